@@ -0,0 +1,82 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use matrix_polynomial_analysis::matrix_family;
+use matrix_polynomial_analysis::polynomial::Polynomial;
+use matrix_polynomial_analysis::power_table::PowerTable;
+use matrix_polynomial_analysis::tolerance::Tolerance;
+use nalgebra::DMatrix;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const MATRICES: usize = 10_000;
+const POWERS: usize = 6;
+
+fn random_matrices(matrix_size: usize) -> Vec<DMatrix<f64>> {
+    let family = matrix_family::from_name("random").expect("random is a family");
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..MATRICES)
+        .map(|_| family.generate(matrix_size, &mut rng))
+        .collect()
+}
+
+// Computing the powers of every matrix into the table.
+fn powers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("powers");
+    for matrix_size in 2..=4 {
+        let matrices = random_matrices(matrix_size);
+        group.bench_with_input(
+            BenchmarkId::new("dynamic", matrix_size),
+            &matrices,
+            |b, matrices| {
+                b.iter(|| {
+                    let mut table = PowerTable::new(matrix_size, POWERS, matrices.len());
+                    for (index, matrix) in matrices.iter().enumerate() {
+                        table.set_matrix_dynamic(index, matrix);
+                    }
+                    black_box(table)
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("fixed", matrix_size),
+            &matrices,
+            |b, matrices| {
+                b.iter(|| black_box(PowerTable::from_matrices(matrices, matrix_size, POWERS)))
+            },
+        );
+    }
+    group.finish();
+}
+
+// Checking a polynomial that passes every matrix, so every block is evaluated in full. The fixed
+// path is benchmarked for every size, though `negative_matrices` only uses it for 2x2.
+fn check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("check");
+    let tolerance = Tolerance::default();
+    for matrix_size in 2..=4 {
+        let table = PowerTable::from_matrices(&random_matrices(matrix_size), matrix_size, POWERS);
+        let polynomial = Polynomial::from_element(POWERS, matrix_size, 1.0);
+        group.bench_with_input(
+            BenchmarkId::new("dynamic", matrix_size),
+            &table,
+            |b, table| {
+                b.iter(|| {
+                    (0..table.blocks()).fold(0, |masks, block| {
+                        masks | table.negative_matrices_dynamic(&polynomial, &tolerance, block)
+                    })
+                })
+            },
+        );
+        let fixed = |block| match matrix_size {
+            2 => table.negative_matrices_fixed::<2>(&polynomial, &tolerance, block),
+            3 => table.negative_matrices_fixed::<3>(&polynomial, &tolerance, block),
+            _ => table.negative_matrices_fixed::<4>(&polynomial, &tolerance, block),
+        };
+        group.bench_function(BenchmarkId::new("fixed", matrix_size), |b| {
+            b.iter(|| (0..table.blocks()).fold(0, |masks, block| masks | fixed(block)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, powers, check);
+criterion_main!(benches);
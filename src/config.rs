@@ -0,0 +1,600 @@
+use crate::current_state::CheckpointConfig;
+use crate::cutting_plane::CuttingPlaneConfig;
+use crate::error::{Error, Result};
+use crate::export::OutputFormat;
+use crate::matrix_family;
+use crate::output::SampleDescription;
+use crate::polynomial::Polynomial;
+use crate::polynomial_verifier::VerifierConfig;
+use crate::progress::ProgressConfig;
+use crate::tolerance::Tolerance;
+use crate::zero_pattern;
+use log::info;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Top level struct to hold the TOML data.
+#[derive(Serialize, Deserialize)]
+struct Data {
+    config: Config,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    Test,
+    Mutate,
+    MapSpace,
+    Resume,
+    CuttingPlane,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Mode::Test => "test",
+            Mode::Mutate => "mutate",
+            Mode::MapSpace => "map-space",
+            Mode::Resume => "resume",
+            Mode::CuttingPlane => "cutting-plane",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Accepts the mode names as well as the numbers 1 to 4 used by older startup files.
+impl<'de> Deserialize<'de> for Mode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawMode {
+            Number(i64),
+            Name(String),
+        }
+
+        let mode = match RawMode::deserialize(deserializer)? {
+            RawMode::Number(1) => Some(Mode::Test),
+            RawMode::Number(2) => Some(Mode::Mutate),
+            RawMode::Number(3) => Some(Mode::MapSpace),
+            RawMode::Number(4) => Some(Mode::Resume),
+            RawMode::Number(_) => None,
+            RawMode::Name(name) => match name.as_str() {
+                "test" => Some(Mode::Test),
+                "mutate" => Some(Mode::Mutate),
+                "map-space" => Some(Mode::MapSpace),
+                "resume" => Some(Mode::Resume),
+                "cutting-plane" => Some(Mode::CuttingPlane),
+                _ => None,
+            },
+        };
+        mode.ok_or_else(|| {
+            serde::de::Error::custom(
+                "mode must be one of \"test\", \"mutate\", \"map-space\", \"resume\" or \"cutting-plane\"",
+            )
+        })
+    }
+}
+
+// Config struct holds to data from the `[config]` section. Which values are needed depends on
+// the mode, see `validate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub matrix_size: usize,
+    #[serde(default)]
+    pub matrices_to_fuzz: usize,
+    #[serde(default)]
+    pub mutated_polynomials_to_evaluate: usize,
+    #[serde(default)]
+    pub polynomial_length: usize,
+    pub mode: Option<Mode>,
+    #[serde(default, deserialize_with = "deserialize_coefficients")]
+    pub starting_polynomial: Vec<f64>,
+    #[serde(default = "default_number_of_generations")]
+    pub number_of_generations: usize,
+    pub seed: Option<u64>,
+    #[serde(default = "default_matrix_families")]
+    pub matrix_families: Vec<String>,
+    #[serde(default)]
+    pub zero_pattern_samples: usize,
+    #[serde(default)]
+    pub exact_arithmetic: bool,
+    #[serde(default)]
+    pub tolerance: Tolerance,
+    #[serde(default)]
+    pub workers: usize,
+    #[serde(default)]
+    pub matrix_search: bool,
+    #[serde(default = "default_matrix_search_restarts")]
+    pub matrix_search_restarts: usize,
+    #[serde(default)]
+    pub cutting_plane: CuttingPlaneConfig,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+    #[serde(default)]
+    pub progress: ProgressConfig,
+}
+
+// Reads a list of coefficients, largest power first, or a polynomial written out as text such as
+// "x^4 + x^3 - 0.5x^2 + x + 1".
+fn deserialize_coefficients<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<f64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawPolynomial {
+        Coefficients(Vec<f64>),
+        Text(String),
+    }
+
+    match RawPolynomial::deserialize(deserializer)? {
+        RawPolynomial::Coefficients(coefficients) => Ok(coefficients),
+        RawPolynomial::Text(text) => {
+            Polynomial::parse_coefficients(&text).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+fn default_number_of_generations() -> usize {
+    1
+}
+
+fn default_matrix_search_restarts() -> usize {
+    20
+}
+
+fn default_matrix_families() -> Vec<String> {
+    vec![String::from("circulant")]
+}
+
+// Settings that may change when a run is resumed, every other one is taken from the state.
+const RESUMABLE_SETTINGS: [&str; 5] =
+    ["mode", "workers", "output_format", "checkpoint", "progress"];
+
+fn invalid(message: String) -> Result<()> {
+    Err(Error::InvalidConfig(message))
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file_contents = fs::read_to_string(path).map_err(|source| Error::io(path, source))?;
+        let data: Data = toml::from_str(&file_contents).map_err(|source| Error::Toml {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(data.config)
+    }
+
+    // Writes the config as a startup file that `load` reads back.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = Data {
+            config: self.clone(),
+        };
+        // Going through a `toml::Value` puts the tables after the plain values as TOML requires.
+        let contents = toml::Value::try_from(&data)
+            .map(|value| value.to_string())
+            .map_err(|source| Error::InvalidConfig(format!("{}: {}", path.display(), source)))?;
+        fs::write(path, contents).map_err(|source| Error::io(path, source))
+    }
+
+    // Checks that everything `mode` needs is present and usable.
+    pub fn validate(&self, mode: Mode) -> Result<()> {
+        if self.matrix_size == 0 {
+            return invalid(String::from("matrix_size must be at least 1"));
+        }
+        if self.matrices_to_fuzz == 0 {
+            return invalid(String::from("matrices_to_fuzz must be at least 1"));
+        }
+        if self.matrix_families.is_empty() {
+            return invalid(String::from(
+                "matrix_families must name at least one family",
+            ));
+        }
+        if let Err(name) = matrix_family::from_names(&self.matrix_families) {
+            return invalid(format!(
+                "unknown matrix family \"{}\", expected one of {:?}",
+                name,
+                matrix_family::FAMILY_NAMES
+            ));
+        }
+        if self.zero_pattern_samples > 0 && self.matrix_size > zero_pattern::MAX_PATTERN_SIZE {
+            return invalid(format!(
+                "zero_pattern_samples only supports matrix_size up to {}",
+                zero_pattern::MAX_PATTERN_SIZE
+            ));
+        }
+        if self.matrix_search && self.matrix_search_restarts == 0 {
+            return invalid(String::from(
+                "matrix_search_restarts must be at least 1 when matrix_search is enabled",
+            ));
+        }
+        self.validate_tolerance()?;
+        if !self.progress.interval_seconds.is_finite() || self.progress.interval_seconds < 0.0 {
+            return invalid(String::from(
+                "progress.interval_seconds must be a finite number of seconds, 0 or more",
+            ));
+        }
+        if !self.checkpoint.interval_seconds.is_finite() || self.checkpoint.interval_seconds < 0.0 {
+            return invalid(String::from(
+                "checkpoint.interval_seconds must be a finite number of seconds, 0 or more",
+            ));
+        }
+
+        match mode {
+            Mode::Test => self.validate_starting_polynomial(mode),
+            Mode::Mutate => {
+                self.validate_mutation()?;
+                self.validate_starting_polynomial(mode)?;
+                let terms = self.starting_polynomial.len();
+                if terms <= self.matrix_size {
+                    return invalid(format!(
+                        "starting_polynomial has {} terms but mutate needs more than matrix_size ({}), \
+                         otherwise every negative coefficient is rejected",
+                        terms, self.matrix_size
+                    ));
+                }
+                Ok(())
+            }
+            Mode::MapSpace => {
+                self.validate_mutation()?;
+                if self.polynomial_length <= self.matrix_size {
+                    return invalid(format!(
+                        "map-space needs polynomial_length set to more than matrix_size ({}) terms",
+                        self.matrix_size
+                    ));
+                }
+                Ok(())
+            }
+            Mode::Resume => {
+                if self.number_of_generations == 0 {
+                    return invalid(String::from("number_of_generations must be at least 1"));
+                }
+                Ok(())
+            }
+            Mode::CuttingPlane => {
+                self.validate_starting_polynomial(mode)?;
+                self.validate_cutting_plane()
+            }
+        }
+    }
+
+    fn validate_mutation(&self) -> Result<()> {
+        if self.mutated_polynomials_to_evaluate == 0 {
+            return invalid(String::from(
+                "mutated_polynomials_to_evaluate must be at least 1",
+            ));
+        }
+        if self.number_of_generations == 0 {
+            return invalid(String::from("number_of_generations must be at least 1"));
+        }
+        Ok(())
+    }
+
+    fn validate_starting_polynomial(&self, mode: Mode) -> Result<()> {
+        if self.starting_polynomial.is_empty() {
+            return invalid(format!(
+                "{} needs a starting_polynomial, in the startup file or as coefficients after the \
+                 command",
+                mode
+            ));
+        }
+        if self
+            .starting_polynomial
+            .iter()
+            .any(|term| !term.is_finite())
+        {
+            return invalid(format!(
+                "starting_polynomial for {} must only contain finite numbers",
+                mode
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_cutting_plane(&self) -> Result<()> {
+        let cutting_plane = &self.cutting_plane;
+        let terms = self.starting_polynomial.len();
+        if cutting_plane.max_iterations == 0 {
+            return invalid(String::from(
+                "cutting_plane.max_iterations must be at least 1",
+            ));
+        }
+        if self.matrix_search_restarts == 0 {
+            return invalid(String::from(
+                "matrix_search_restarts must be at least 1 for the cutting plane mode",
+            ));
+        }
+        if let Some(index) = cutting_plane
+            .coefficients
+            .iter()
+            .find(|index| **index >= terms)
+        {
+            return invalid(format!(
+                "cutting_plane.coefficients has index {} but starting_polynomial only has {} terms",
+                index, terms
+            ));
+        }
+        if cutting_plane
+            .lowered_coefficients(terms, self.matrix_size)
+            .is_empty()
+        {
+            return invalid(format!(
+                "starting_polynomial has {} terms, cutting-plane needs more than twice matrix_size ({}) \
+                 or a list of cutting_plane.coefficients",
+                terms, self.matrix_size
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_tolerance(&self) -> Result<()> {
+        let tolerance = &self.tolerance;
+        if tolerance.absolute.is_nan()
+            || tolerance.relative.is_nan()
+            || tolerance.absolute < 0.0
+            || tolerance.relative < 0.0
+        {
+            return invalid(String::from(
+                "tolerance.absolute and tolerance.relative must not be negative",
+            ));
+        }
+        if tolerance.zero_coefficient.is_nan() || tolerance.zero_coefficient < 0.0 {
+            return invalid(String::from(
+                "tolerance.zero_coefficient must not be negative",
+            ));
+        }
+        if !tolerance.interesting_threshold.is_finite() {
+            return invalid(String::from(
+                "tolerance.interesting_threshold must be a finite number",
+            ));
+        }
+        Ok(())
+    }
+
+    // Uses the configured seed, or picks a fresh one so the run can still be repeated later.
+    pub fn seed(&self) -> u64 {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        info!("Using seed {}", seed);
+        seed
+    }
+
+    // Describes the matrices the verifier draws, zero pattern matrices are counted per pattern.
+    pub fn matrix_sample(&self) -> Vec<SampleDescription> {
+        let mut sample = Vec::new();
+        if self.zero_pattern_samples > 0 {
+            sample.push(SampleDescription {
+                name: String::from("zero_pattern"),
+                description: String::from(
+                    "random positive weights on every zero pattern, per pattern",
+                ),
+                matrices: self.zero_pattern_samples,
+            });
+        }
+        if let Ok(families) = matrix_family::from_names(&self.matrix_families) {
+            let matrices_per_family = self.matrices_to_fuzz / families.len().max(1);
+            for family in families {
+                sample.push(SampleDescription {
+                    name: String::from(family.name()),
+                    description: String::from(family.description()),
+                    matrices: matrices_per_family,
+                });
+            }
+        }
+        sample
+    }
+
+    // The settings of `self` that differ from the run `stored` in a resumed state, written as
+    // `name = value (run has stored value)`. Settings that may change on resume are skipped, as is
+    // the seed when `self` has none.
+    pub fn resume_differences(&self, stored: &Config) -> Vec<String> {
+        let (current, stored) = match (serde_json::to_value(self), serde_json::to_value(stored)) {
+            (Ok(Value::Object(current)), Ok(Value::Object(stored))) => (current, stored),
+            _ => return Vec::new(),
+        };
+        current
+            .iter()
+            .filter(|(name, _)| !RESUMABLE_SETTINGS.contains(&name.as_str()))
+            .filter(|(name, _)| *name != "seed" || self.seed.is_some())
+            .filter_map(|(name, value)| {
+                let stored_value = stored.get(name).unwrap_or(&Value::Null);
+                (value != stored_value)
+                    .then(|| format!("{} = {} (run has {})", name, value, stored_value))
+            })
+            .collect()
+    }
+
+    // The config to resume the run `self` with: everything from the run except the settings that
+    // may change, which come from `current`.
+    pub fn resumed_with(&self, current: &Config) -> Config {
+        let mut resumed = self.clone();
+        resumed.mode = current.mode;
+        resumed.workers = current.workers;
+        resumed.output_format = current.output_format;
+        resumed.checkpoint = current.checkpoint.clone();
+        resumed.progress = current.progress.clone();
+        resumed
+    }
+
+    pub fn verifier_config(&self, seed: u64) -> Result<VerifierConfig> {
+        let families = matrix_family::from_names(&self.matrix_families)
+            .map_err(|name| Error::InvalidConfig(format!("unknown matrix family \"{}\"", name)))?;
+        Ok(VerifierConfig {
+            matrices_to_fuzz: self.matrices_to_fuzz,
+            families,
+            zero_pattern_samples: self.zero_pattern_samples,
+            exact_arithmetic: self.exact_arithmetic,
+            tolerance: self.tolerance,
+            seed,
+            workers: self.workers,
+            matrix_search: self.matrix_search,
+            matrix_search_restarts: self.matrix_search_restarts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    // A config every mode accepts.
+    fn valid() -> Config {
+        config(
+            "matrix_size = 2\nmatrices_to_fuzz = 10\nmutated_polynomials_to_evaluate = 4\n\
+             polynomial_length = 5\nstarting_polynomial = [1, 1, 1, 1, 1]",
+        )
+    }
+
+    fn assert_rejected(config: &Config, mode: Mode) {
+        assert!(
+            matches!(config.validate(mode), Err(Error::InvalidConfig(_))),
+            "{} accepted {:?}",
+            mode,
+            config
+        );
+    }
+
+    // Applies `change` to a valid config and checks that every mode in `modes` rejects it.
+    fn rejects(modes: &[Mode], change: impl Fn(&mut Config)) {
+        let mut config = valid();
+        change(&mut config);
+        for mode in modes {
+            assert_rejected(&config, *mode);
+        }
+    }
+
+    const ALL_MODES: [Mode; 5] = [
+        Mode::Test,
+        Mode::Mutate,
+        Mode::MapSpace,
+        Mode::Resume,
+        Mode::CuttingPlane,
+    ];
+
+    #[test]
+    fn every_mode_accepts_a_valid_config() {
+        for mode in ALL_MODES {
+            assert!(valid().validate(mode).is_ok(), "{}", mode);
+        }
+    }
+
+    #[test]
+    fn every_mode_checks_the_matrices() {
+        rejects(&ALL_MODES, |config| config.matrix_size = 0);
+        rejects(&ALL_MODES, |config| config.matrices_to_fuzz = 0);
+        rejects(&ALL_MODES, |config| config.matrix_families.clear());
+        rejects(&ALL_MODES, |config| {
+            config.matrix_families = vec![String::from("hankel")]
+        });
+        rejects(&ALL_MODES, |config| {
+            config.matrix_search = true;
+            config.matrix_search_restarts = 0;
+        });
+    }
+
+    #[test]
+    fn every_mode_checks_tolerances_and_intervals() {
+        rejects(&ALL_MODES, |config| config.tolerance.absolute = -1.0);
+        rejects(&ALL_MODES, |config| config.tolerance.relative = f64::NAN);
+        rejects(&ALL_MODES, |config| {
+            config.tolerance.zero_coefficient = -1.0
+        });
+        rejects(&ALL_MODES, |config| {
+            config.tolerance.interesting_threshold = f64::INFINITY
+        });
+        rejects(&ALL_MODES, |config| config.progress.interval_seconds = -1.0);
+        rejects(&ALL_MODES, |config| {
+            config.checkpoint.interval_seconds = f64::NAN
+        });
+    }
+
+    #[test]
+    fn test_needs_a_finite_starting_polynomial() {
+        rejects(&[Mode::Test], |config| config.starting_polynomial.clear());
+        rejects(&[Mode::Test], |config| {
+            config.starting_polynomial[1] = f64::INFINITY
+        });
+    }
+
+    #[test]
+    fn mutate_needs_a_long_enough_starting_polynomial() {
+        let mutate = [Mode::Mutate];
+        rejects(&mutate, |config| config.starting_polynomial.clear());
+        rejects(&mutate, |config| config.starting_polynomial[0] = f64::NAN);
+        rejects(&mutate, |config| {
+            config.starting_polynomial = vec![1.0, 1.0]
+        });
+        rejects(&mutate, |config| config.mutated_polynomials_to_evaluate = 0);
+        rejects(&mutate, |config| config.number_of_generations = 0);
+    }
+
+    #[test]
+    fn map_space_needs_a_polynomial_length() {
+        let map_space = [Mode::MapSpace];
+        rejects(&map_space, |config| config.polynomial_length = 0);
+        rejects(&map_space, |config| config.polynomial_length = 2);
+        rejects(&map_space, |config| {
+            config.mutated_polynomials_to_evaluate = 0
+        });
+        rejects(&map_space, |config| config.number_of_generations = 0);
+        // It does not start from the starting polynomial.
+        let mut config = valid();
+        config.starting_polynomial.clear();
+        assert!(config.validate(Mode::MapSpace).is_ok());
+    }
+
+    #[test]
+    fn resume_needs_generations() {
+        rejects(&[Mode::Resume], |config| config.number_of_generations = 0);
+        // The starting polynomial comes from the state.
+        let mut config = valid();
+        config.starting_polynomial.clear();
+        assert!(config.validate(Mode::Resume).is_ok());
+    }
+
+    #[test]
+    fn cutting_plane_needs_coefficients_to_lower() {
+        let cutting_plane = [Mode::CuttingPlane];
+        rejects(&cutting_plane, |config| config.starting_polynomial.clear());
+        rejects(&cutting_plane, |config| {
+            config.cutting_plane.max_iterations = 0
+        });
+        rejects(&cutting_plane, |config| config.matrix_search_restarts = 0);
+        rejects(&cutting_plane, |config| {
+            config.cutting_plane.coefficients = vec![5]
+        });
+        // Five terms have no inner coefficients for 2x2 matrices.
+        rejects(&cutting_plane, |config| {
+            config.starting_polynomial.truncate(4)
+        });
+        let mut config = valid();
+        config.starting_polynomial.truncate(4);
+        config.cutting_plane.coefficients = vec![2];
+        assert!(config.validate(Mode::CuttingPlane).is_ok());
+    }
+
+    #[test]
+    fn zero_patterns_are_limited_to_eight_by_eight() {
+        let mut config = config(
+            "matrix_size = 9\nmatrices_to_fuzz = 10\nzero_pattern_samples = 1\n\
+             starting_polynomial = [1, 1, 1]",
+        );
+        assert!(matches!(
+            config.validate(Mode::Test),
+            Err(Error::InvalidConfig(_))
+        ));
+        config.matrix_size = zero_pattern::MAX_PATTERN_SIZE;
+        assert!(config.validate(Mode::Test).is_ok());
+        config.matrix_size = 9;
+        config.zero_pattern_samples = 0;
+        assert!(config.validate(Mode::Test).is_ok());
+    }
+}
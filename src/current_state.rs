@@ -1,19 +1,109 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::output::{GenerationReport, Provenance};
 use crate::polynomial::Polynomial;
+use crate::polynomial_verifier::FuzzedMatrices;
 use itertools::Itertools;
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::fs::File;
+use serde_json::value::RawValue;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// How often the run state is saved and how many earlier saves are kept, loaded from the
+// `[config.checkpoint]` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    // Seconds between saves while combinations finish, 0 saves after every combination. The end
+    // of every generation is always saved.
+    pub interval_seconds: f64,
+    // Earlier checkpoints kept as `<state>.1`, `<state>.2`, ..., newest first. Loading falls back
+    // to them when the latest one is damaged.
+    pub keep: usize,
+    // Store the fuzzed matrices in the state so `resume` uses them instead of drawing them again
+    // from the seed. Makes every checkpoint as large as the sample.
+    pub store_matrices: bool,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        CheckpointConfig {
+            interval_seconds: 30.0,
+            keep: 2,
+            store_matrices: false,
+        }
+    }
+}
+
+// A state file: the serialized state exactly as written together with its checksum. Files from
+// before checkpoints had a checksum hold the bare state.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<'a> {
+    checksum: String,
+    #[serde(borrow)]
+    state: &'a RawValue,
+}
+
+// 64 bit FNV-1a, enough to notice a torn or truncated write.
+fn checksum(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// `<state>.<suffix>` next to the state file.
+fn sibling(state_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(state_path.as_os_str());
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct CurrentState {
     pub starting_mutated_polynomials: Vec<Polynomial>,
     pub combinations_left: Vec<Vec<Vec<usize>>>,
     pub interesting_polynomials: Vec<Polynomial>,
+    // Where each of `interesting_polynomials` came from, None for states saved before this was
+    // recorded.
+    #[serde(default)]
+    pub provenance: Vec<Option<Provenance>>,
     pub current_generation: usize,
+    #[serde(default)]
+    pub seed: u64,
+    // Time spent on the current generation so far, carried over when resuming.
+    #[serde(default)]
+    pub generation_seconds: f64,
+    #[serde(default)]
+    pub generations: Vec<GenerationReport>,
+    // The config of the run with its seed, None for states saved before this was recorded.
+    #[serde(default)]
+    pub config: Option<Config>,
+    // The fuzzed matrices when `checkpoint.store_matrices` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matrices: Option<FuzzedMatrices>,
+    // Where and how often the state is saved, not part of the state itself.
+    #[serde(skip, default = "default_state_path")]
+    state_path: PathBuf,
+    #[serde(skip)]
+    checkpoint: CheckpointConfig,
+    #[serde(skip)]
+    last_checkpoint: Option<Instant>,
+}
+
+fn default_state_path() -> PathBuf {
+    PathBuf::from("state.json")
 }
 
 impl CurrentState {
-    pub fn new(polynomial_length: usize, current_generation: usize) -> Self {
+    pub fn new(polynomial_length: usize, current_generation: usize, seed: u64) -> Self {
         let mut combinations_left = Vec::new();
         for i in 0..polynomial_length {
             combinations_left.push(Vec::new());
@@ -24,34 +114,329 @@ impl CurrentState {
 
         CurrentState {
             starting_mutated_polynomials: Vec::new(),
-            combinations_left: combinations_left,
+            combinations_left,
             interesting_polynomials: Vec::new(),
+            provenance: Vec::new(),
             current_generation,
+            seed,
+            generation_seconds: 0.0,
+            generations: Vec::new(),
+            config: None,
+            matrices: None,
+            state_path: default_state_path(),
+            checkpoint: CheckpointConfig::default(),
+            last_checkpoint: None,
         }
     }
 
-    pub fn remove_combination(&mut self, combination: &Vec<usize>, combination_length: usize) {
+    pub fn remove_combination(
+        &mut self,
+        combination: &Vec<usize>,
+        combination_length: usize,
+    ) -> Result<()> {
         self.combinations_left[combination_length].retain(|x| x != combination);
-        self.save_state();
+        let due = self
+            .last_checkpoint
+            .is_none_or(|last| last.elapsed().as_secs_f64() >= self.checkpoint.interval_seconds);
+        if due {
+            self.save_state()?;
+        }
+        Ok(())
+    }
+
+    // Combinations minimized in a generation: every subset of the coefficients except the empty
+    // one and all of them.
+    pub fn combinations_total(&self) -> usize {
+        let coefficients = self.combinations_left.len();
+        (1..coefficients)
+            .map(|length| (0..coefficients).combinations(length).count())
+            .sum()
+    }
+
+    // Combinations of the current generation still to minimize, the empty one is never minimized.
+    pub fn combinations_remaining(&self) -> usize {
+        self.combinations_left
+            .iter()
+            .flatten()
+            .filter(|combination| !combination.is_empty())
+            .count()
+    }
+
+    // Records a polynomial found by minimizing `combination` of `parent`.
+    pub fn add_interesting_polynomial(
+        &mut self,
+        polynomial: Polynomial,
+        parent: &Polynomial,
+        combination: &[usize],
+    ) {
+        self.interesting_polynomials.push(polynomial);
+        self.provenance.push(Some(Provenance {
+            parent: parent.clone(),
+            combination: combination.to_vec(),
+            generation: self.current_generation,
+        }));
     }
 
-    pub fn finish_generation(&mut self) {
-        self.interesting_polynomials =
-            Polynomial::collapse_polynomials(&self.interesting_polynomials);
+    pub fn finish_generation(&mut self) -> Result<()> {
+        let collapsed = Polynomial::collapse_polynomials_indexed(&self.interesting_polynomials);
+        self.provenance = collapsed
+            .iter()
+            .map(|(index, _)| self.provenance[*index].clone())
+            .collect();
+        self.interesting_polynomials = collapsed
+            .into_iter()
+            .map(|(_, polynomial)| polynomial)
+            .collect();
         self.starting_mutated_polynomials = self.interesting_polynomials.clone();
+        self.generations.push(GenerationReport {
+            generation: self.current_generation,
+            elapsed_seconds: self.generation_seconds,
+            interesting_polynomials: self.interesting_polynomials.len(),
+        });
+        self.generation_seconds = 0.0;
         self.current_generation += 1;
-        self.save_state();
+        self.save_state()
+    }
+
+    pub fn set_state_path(&mut self, state_path: &Path) {
+        self.state_path = state_path.to_path_buf();
+    }
+
+    pub fn set_checkpoint_config(&mut self, checkpoint: &CheckpointConfig) {
+        self.checkpoint = checkpoint.clone();
+    }
+
+    // Writes the state to a temporary file and renames it over the state file, so a crash leaves
+    // either the old or the new checkpoint. The replaced checkpoint is kept as `<state>.1` and the
+    // older ones shift up, dropping any beyond `keep`.
+    pub fn save_state(&mut self) -> Result<()> {
+        let state =
+            serde_json::to_string(&self).map_err(|source| Error::json(&self.state_path, source))?;
+        let state =
+            RawValue::from_string(state).map_err(|source| Error::json(&self.state_path, source))?;
+        let json_object = serde_json::to_string(&Checkpoint {
+            checksum: checksum(state.get().as_bytes()),
+            state: &state,
+        })
+        .map_err(|source| Error::json(&self.state_path, source))?;
+
+        let temporary_path = sibling(&self.state_path, "tmp");
+        write_synced(&temporary_path, json_object.as_bytes())
+            .map_err(|source| Error::io(&temporary_path, source))?;
+        self.rotate_checkpoints()?;
+        fs::rename(&temporary_path, &self.state_path)
+            .map_err(|source| Error::io(&self.state_path, source))?;
+        self.last_checkpoint = Some(Instant::now());
+        Ok(())
+    }
+
+    fn rotate_checkpoints(&self) -> Result<()> {
+        if self.checkpoint.keep == 0 || !self.state_path.exists() {
+            return Ok(());
+        }
+        for i in (1..self.checkpoint.keep).rev() {
+            let older = sibling(&self.state_path, &i.to_string());
+            if older.exists() {
+                let newer = sibling(&self.state_path, &(i + 1).to_string());
+                fs::rename(&older, &newer).map_err(|source| Error::io(&newer, source))?;
+            }
+        }
+        let previous = sibling(&self.state_path, "1");
+        fs::rename(&self.state_path, &previous).map_err(|source| Error::io(&previous, source))
+    }
+
+    // Loads the newest checkpoint that reads back intact. When the state file is missing or
+    // damaged this falls back to a finished temporary file left by a crash during `save_state`,
+    // then to `<state>.1`, `<state>.2`, ... The error is the one of the state file itself when
+    // none of them can be used.
+    pub fn load_state(state_path: &Path) -> Result<Self> {
+        let mut first_error = None;
+        let temporary_path = sibling(state_path, "tmp");
+        let candidates = std::iter::once(state_path.to_path_buf())
+            .chain(Some(temporary_path).filter(|path| path.exists()))
+            .chain(
+                (1..)
+                    .map(|i| sibling(state_path, &i.to_string()))
+                    .take_while(|path| path.exists()),
+            );
+        for path in candidates {
+            match Self::read_checkpoint(&path) {
+                Ok(mut current_state) => {
+                    if let Some(error) = &first_error {
+                        warn!("Resuming from {} because {}", path.display(), error);
+                    }
+                    current_state.set_state_path(state_path);
+                    current_state
+                        .provenance
+                        .resize(current_state.interesting_polynomials.len(), None);
+                    return Ok(current_state);
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        Err(first_error.expect("the state file is always tried"))
+    }
+
+    fn read_checkpoint(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::io(path, source))?;
+        match serde_json::from_str::<Checkpoint>(&contents) {
+            Ok(checkpoint) => {
+                let state = checkpoint.state.get();
+                if checksum(state.as_bytes()) != checkpoint.checksum {
+                    return Err(Error::InvalidState(format!(
+                        "{} does not match its checksum",
+                        path.display()
+                    )));
+                }
+                serde_json::from_str(state).map_err(|source| Error::json(path, source))
+            }
+            // A state file written before checkpoints had a checksum.
+            Err(_) => serde_json::from_str(&contents).map_err(|source| Error::json(path, source)),
+        }
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty directory with a state that saves to `state.json` in it and keeps `keep` earlier
+    // checkpoints.
+    fn state(name: &str, keep: usize) -> (PathBuf, CurrentState) {
+        let directory = std::env::temp_dir().join(format!(
+            "matrix_polynomial_analysis-state-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let mut current_state = CurrentState::new(3, 0, 5);
+        current_state.set_state_path(&directory.join("state.json"));
+        current_state.set_checkpoint_config(&CheckpointConfig {
+            keep,
+            ..CheckpointConfig::default()
+        });
+        (directory, current_state)
+    }
+
+    // Saves generations 0 to `saves - 1`, so the state file holds the last one.
+    fn save_generations(current_state: &mut CurrentState, saves: usize) {
+        for generation in 0..saves {
+            current_state.current_generation = generation;
+            current_state.save_state().unwrap();
+        }
+    }
+
+    fn generation(path: &Path) -> usize {
+        CurrentState::read_checkpoint(path)
+            .unwrap()
+            .current_generation
+    }
+
+    // Changes the generation stored in the file but not its checksum.
+    fn tamper(path: &Path) {
+        let contents = fs::read_to_string(path).unwrap();
+        let tampered = contents.replacen("\"current_generation\":", "\"current_generation\":1", 1);
+        assert_ne!(contents, tampered);
+        fs::write(path, tampered).unwrap();
     }
 
-    pub fn save_state(&self) {
-        let json_object =
-            serde_json::to_string(&self).expect("Object will be converted to JSON string");
-        File::create("state.json").expect("file should open read only");
-        fs::write("state.json", json_object).expect("file should open read only");
+    #[test]
+    fn rotation_keeps_the_newest_checkpoints() {
+        let (directory, mut current_state) = state("rotation", 2);
+        let state_path = directory.join("state.json");
+        save_generations(&mut current_state, 5);
+        assert_eq!(generation(&state_path), 4);
+        assert_eq!(generation(&sibling(&state_path, "1")), 3);
+        assert_eq!(generation(&sibling(&state_path, "2")), 2);
+        assert!(!sibling(&state_path, "3").exists());
+        assert!(!sibling(&state_path, "tmp").exists());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let (directory, mut current_state) = state("no-rotation", 0);
+        save_generations(&mut current_state, 3);
+        assert!(!sibling(&directory.join("state.json"), "1").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn checksum_mismatch_falls_back_to_an_earlier_checkpoint() {
+        let (directory, mut current_state) = state("checksum", 2);
+        let state_path = directory.join("state.json");
+        save_generations(&mut current_state, 3);
+        tamper(&state_path);
+        assert!(matches!(
+            CurrentState::read_checkpoint(&state_path),
+            Err(Error::InvalidState(_))
+        ));
+        let loaded = CurrentState::load_state(&state_path).unwrap();
+        assert_eq!(loaded.current_generation, 1);
+
+        // A truncated `.1` is skipped as well.
+        let previous = sibling(&state_path, "1");
+        let contents = fs::read_to_string(&previous).unwrap();
+        fs::write(&previous, &contents[..contents.len() / 2]).unwrap();
+        assert_eq!(
+            CurrentState::load_state(&state_path)
+                .unwrap()
+                .current_generation,
+            0
+        );
+
+        // With nothing intact the error is the one of the state file.
+        tamper(&sibling(&state_path, "2"));
+        assert!(matches!(
+            CurrentState::load_state(&state_path),
+            Err(Error::InvalidState(message)) if message.contains("state.json does not")
+        ));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn finished_temporary_file_is_loaded_before_older_checkpoints() {
+        let (directory, mut current_state) = state("temporary", 2);
+        let state_path = directory.join("state.json");
+        save_generations(&mut current_state, 2);
+        // A crash between writing the temporary file and renaming it over the state file, after
+        // the state file was rotated away.
+        current_state.current_generation = 7;
+        current_state.save_state().unwrap();
+        fs::rename(&state_path, sibling(&state_path, "tmp")).unwrap();
+        let loaded = CurrentState::load_state(&state_path).unwrap();
+        assert_eq!(loaded.current_generation, 7);
+        assert_eq!(loaded.state_path, state_path);
+
+        // A damaged temporary file falls back to `.1`.
+        tamper(&sibling(&state_path, "tmp"));
+        assert_eq!(
+            CurrentState::load_state(&state_path)
+                .unwrap()
+                .current_generation,
+            1
+        );
+        fs::remove_dir_all(&directory).unwrap();
     }
 
-    pub fn load_state() -> Self {
-        let file = File::open("state.json").expect("file should open read only");
-        serde_json::from_reader(file).expect("File was not able to be read")
+    #[test]
+    fn loads_states_without_a_checksum() {
+        let (directory, mut current_state) = state("bare", 2);
+        let state_path = directory.join("state.json");
+        current_state.current_generation = 3;
+        fs::write(&state_path, serde_json::to_string(&current_state).unwrap()).unwrap();
+        assert_eq!(
+            CurrentState::load_state(&state_path)
+                .unwrap()
+                .current_generation,
+            3
+        );
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
@@ -0,0 +1,349 @@
+// The polynomials that keep p(A) nonnegative for a fixed set of matrices form a polyhedral cone,
+// since p(A) is linear in the coefficients. The cutting plane mode lowers the chosen coefficients
+// of a polynomial as far as a small set of matrices allows by solving a linear program, then looks
+// for matrices the resulting polynomial fails on, first in the verifier's sample and then with the
+// verifier's local search over nonnegative matrices. Every matrix found is added to the program and
+// the loop repeats until nothing more is found, at which point `test --matrix-search` with the same
+// config accepts the polynomial.
+use crate::linear_program::{LinearProgram, Solution};
+use crate::matrix_generator;
+use crate::polynomial::Polynomial;
+use crate::polynomial_verifier::{self, PolynomialVerifier};
+use crate::tolerance::Tolerance;
+use log::{debug, info};
+use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
+
+// The constraints of a matrix require its entries of p(A) to be this much above the allowance, as
+// a fraction of the largest coefficient of the constraint. Without the margin the optimum sits
+// exactly on the constraint and rounding makes the same matrix fail again.
+const CUT_MARGIN: f64 = 1e-9;
+
+// Settings of the cutting plane mode, loaded from the `[config.cutting_plane]` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CuttingPlaneConfig {
+    // Indices of the coefficients to lower, largest power first. Empty lowers every coefficient
+    // that is allowed to be negative.
+    pub coefficients: Vec<usize>,
+    pub max_iterations: usize,
+    // Failing matrices of the sample added to the program in each iteration.
+    pub cuts_per_iteration: usize,
+}
+
+impl Default for CuttingPlaneConfig {
+    fn default() -> Self {
+        CuttingPlaneConfig {
+            coefficients: Vec::new(),
+            max_iterations: 200,
+            cuts_per_iteration: 16,
+        }
+    }
+}
+
+impl CuttingPlaneConfig {
+    // The coefficients that are lowered for a polynomial with `length` terms. Unless configured
+    // these are the ones outside the first and last `matrix_size` terms, which have to stay
+    // nonnegative.
+    pub fn lowered_coefficients(&self, length: usize, matrix_size: usize) -> Vec<usize> {
+        if !self.coefficients.is_empty() {
+            return self.coefficients.clone();
+        }
+        (0..length)
+            .filter(|i| *i >= matrix_size && *i + matrix_size < length)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    // Neither the sample nor the verifier's local search found a failing matrix.
+    Converged,
+    // `max_iterations` ran out while failing matrices were still being found.
+    IterationLimit,
+    // No polynomial passes the matrices found so far, which only happens when the starting
+    // polynomial fails one of them.
+    Infeasible,
+    // The matrices found so far do not bound the coefficients.
+    Unbounded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CuttingPlaneResult {
+    pub status: Status,
+    // The polynomial of the last linear program, None when it had no optimum.
+    pub polynomial: Option<Polynomial>,
+    pub iterations: usize,
+    // Matrices added to the program from the sample and from the local search.
+    pub sample_cuts: usize,
+    pub search_cuts: usize,
+}
+
+// The linear program over the lowered coefficients, with the other coefficients held at their
+// starting values.
+struct Cuts<'a> {
+    polynomial: &'a Polynomial,
+    coefficients: &'a [usize],
+    tolerance: &'a Tolerance,
+    program: LinearProgram,
+}
+
+impl<'a> Cuts<'a> {
+    fn new(
+        polynomial: &'a Polynomial,
+        coefficients: &'a [usize],
+        tolerance: &'a Tolerance,
+    ) -> Self {
+        let length = polynomial.len();
+        let size = polynomial.get_size();
+        let mut program = LinearProgram::new(coefficients.len());
+        for (variable, i) in coefficients.iter().enumerate() {
+            // Coefficients are only ever lowered, and the first and last terms stay nonnegative.
+            let lower = if *i < size || *i + size >= length {
+                0.0
+            } else {
+                f64::NEG_INFINITY
+            };
+            program.set_bounds(variable, lower, polynomial[*i]);
+        }
+        Cuts {
+            polynomial,
+            coefficients,
+            tolerance,
+            program,
+        }
+    }
+
+    // Requires every entry of p(A) to stay above the allowance, taken at `candidate`.
+    fn add(&mut self, matrix: &DMatrix<f64>, candidate: &Polynomial) {
+        let length = self.polynomial.len();
+        let matrix_powers = matrix_generator::generate_matrix_powers(matrix, length);
+        let allowance = self
+            .tolerance
+            .allowance(&candidate.evaluate_matrix_with_powers(&matrix_powers));
+        // p(A) of the coefficients that are held fixed.
+        let mut fixed_polynomial = self.polynomial.clone();
+        for i in self.coefficients {
+            fixed_polynomial[*i] = 0.0;
+        }
+        let fixed_values = fixed_polynomial.evaluate_matrix_with_powers(&matrix_powers);
+        for (entry, fixed) in fixed_values.iter().enumerate() {
+            // The coefficient at index i multiplies A^(length - 1 - i).
+            let a: Vec<f64> = self
+                .coefficients
+                .iter()
+                .map(|i| matrix_powers[length - 1 - i][entry])
+                .collect();
+            let scale = a.iter().fold(0.0f64, |max, value| max.max(value.abs()));
+            self.program
+                .add_lower_constraint(&a, -allowance - fixed + CUT_MARGIN * scale);
+        }
+    }
+
+    fn solve(&self) -> Solution {
+        self.program.minimize(&vec![1.0; self.coefficients.len()])
+    }
+
+    fn polynomial(&self, x: &[f64]) -> Polynomial {
+        let mut polynomial = self.polynomial.clone();
+        for (i, value) in self.coefficients.iter().zip(x) {
+            polynomial[*i] = *value;
+        }
+        polynomial
+    }
+}
+
+// Lowers the sum of `config`'s coefficients of `polynomial` as far as every matrix found allows.
+// The program starts from the identity and permutation matrices, and each iteration adds the
+// failing matrices of the verifier's sample, or the matrix of `find_violating_matrix` once the
+// sample passes, so the search runs with the restarts, seed and tolerance of `test`.
+pub fn minimize(
+    polynomial: &Polynomial,
+    config: &CuttingPlaneConfig,
+    polynomial_verifier: &PolynomialVerifier,
+) -> CuttingPlaneResult {
+    let coefficients = config.lowered_coefficients(polynomial.len(), polynomial.get_size());
+    let tolerance = polynomial_verifier.tolerance();
+    let mut cuts = Cuts::new(polynomial, &coefficients, tolerance);
+    for matrix in polynomial_verifier::simple_matrices(polynomial.get_size()) {
+        cuts.add(&matrix, polynomial);
+    }
+
+    let mut result = CuttingPlaneResult {
+        status: Status::IterationLimit,
+        polynomial: None,
+        iterations: 0,
+        sample_cuts: 0,
+        search_cuts: 0,
+    };
+    while result.iterations < config.max_iterations {
+        result.iterations += 1;
+        let candidate = match cuts.solve() {
+            Solution::Optimal { x, .. } => cuts.polynomial(&x),
+            Solution::Infeasible => {
+                result.status = Status::Infeasible;
+                result.polynomial = None;
+                return result;
+            }
+            Solution::Unbounded => {
+                result.status = Status::Unbounded;
+                result.polynomial = None;
+                return result;
+            }
+        };
+        debug!("Iteration {} candidate {}", result.iterations, candidate);
+
+        let mut matrices: Vec<DMatrix<f64>> = polynomial_verifier
+            .violating_matrices(&candidate, config.cuts_per_iteration)
+            .into_iter()
+            .map(|counterexample| counterexample.matrix)
+            .collect();
+        result.sample_cuts += matrices.len();
+        if matrices.is_empty() {
+            if let Some(counterexample) = polynomial_verifier.find_violating_matrix(&candidate) {
+                debug!("Local search found {}", counterexample.min_entry);
+                matrices.push(counterexample.matrix);
+                result.search_cuts += 1;
+            }
+        }
+
+        if matrices.is_empty() {
+            result.status = Status::Converged;
+            result.polynomial = Some(candidate);
+            break;
+        }
+        for matrix in &matrices {
+            cuts.add(matrix, &candidate);
+        }
+        result.polynomial = Some(candidate);
+    }
+    info!(
+        "Cutting plane finished after {} iterations with {} sample and {} search cuts",
+        result.iterations, result.sample_cuts, result.search_cuts
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_family;
+    use crate::polynomial_verifier::{FuzzedMatrices, VerifierConfig};
+
+    // x^4 + c x^2 + 4 on 1-by-1 matrices, nonnegative on x >= 0 exactly when c >= -4, with the
+    // minimum at x = sqrt(2) for c = -4.
+    fn quartic() -> Polynomial {
+        Polynomial::from_vec(vec![1.0, 0.0, 1.0, 0.0, 4.0], 1)
+    }
+
+    // A verifier with the given sample of `size`-by-`size` matrices.
+    fn verifier(size: usize, matrices: Vec<DMatrix<f64>>) -> PolynomialVerifier {
+        let config = VerifierConfig {
+            matrices_to_fuzz: matrices.len(),
+            families: matrix_family::from_names(&[String::from("random")]).unwrap(),
+            zero_pattern_samples: 0,
+            exact_arithmetic: false,
+            tolerance: Tolerance::default(),
+            seed: 3,
+            workers: 1,
+            matrix_search: true,
+            matrix_search_restarts: 20,
+        };
+        let fuzzed_matrices = FuzzedMatrices {
+            matrix_size: size,
+            families: vec![String::from("random"); matrices.len()],
+            matrices,
+        };
+        PolynomialVerifier::from_fuzzed_matrices(&config, &fuzzed_matrices, 5).unwrap()
+    }
+
+    fn config(coefficients: Vec<usize>) -> CuttingPlaneConfig {
+        CuttingPlaneConfig {
+            coefficients,
+            ..CuttingPlaneConfig::default()
+        }
+    }
+
+    #[test]
+    fn lowers_the_inner_coefficients_by_default() {
+        assert_eq!(config(Vec::new()).lowered_coefficients(6, 2), vec![2, 3]);
+        assert_eq!(config(Vec::new()).lowered_coefficients(5, 1), vec![1, 2, 3]);
+        assert!(config(Vec::new()).lowered_coefficients(4, 2).is_empty());
+        assert_eq!(config(vec![0, 4]).lowered_coefficients(6, 2), vec![0, 4]);
+    }
+
+    #[test]
+    fn a_cut_bounds_the_coefficients_by_the_matrix() {
+        let polynomial = quartic();
+        let tolerance = Tolerance::default();
+        let coefficients = [2];
+        let mut cuts = Cuts::new(&polynomial, &coefficients, &tolerance);
+        // Only the bounds hold c <= 1, so nothing stops it from going down.
+        assert_eq!(cuts.solve(), Solution::Unbounded);
+
+        // At A = 2, 16 + 4c + 4 >= 0 gives c >= -5, plus the margin.
+        cuts.add(&DMatrix::from_element(1, 1, 2.0), &polynomial);
+        let x = match cuts.solve() {
+            Solution::Optimal { x, .. } => x,
+            other => panic!("expected an optimum, got {:?}", other),
+        };
+        assert!((x[0] + 5.0).abs() < 1e-6, "{:?}", x);
+        let candidate = cuts.polynomial(&x);
+        assert_eq!(candidate.coefficients()[..2], [1.0, 0.0]);
+        assert!(candidate.evaluate(2.0) >= 0.0);
+
+        // A second matrix that binds harder moves the optimum, at A = 1.5 c >= -(1.5^2 + 4 / 1.5^2).
+        cuts.add(&DMatrix::from_element(1, 1, 1.5), &candidate);
+        match cuts.solve() {
+            Solution::Optimal { x, .. } => {
+                let bound = -(2.25 + 4.0 / 2.25);
+                assert!((x[0] - bound).abs() < 1e-6, "{:?}", x);
+            }
+            other => panic!("expected an optimum, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn first_and_last_terms_stay_nonnegative() {
+        let polynomial = quartic();
+        let tolerance = Tolerance::default();
+        let coefficients = [0, 4];
+        let cuts = Cuts::new(&polynomial, &coefficients, &tolerance);
+        match cuts.solve() {
+            Solution::Optimal { x, value } => {
+                assert_eq!(x, vec![0.0, 0.0]);
+                assert_eq!(value, 0.0);
+            }
+            other => panic!("expected an optimum, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn converges_to_the_extreme_polynomial() {
+        // On 2-by-2 matrices x^4 + c x^2 + 4 needs c >= 0: for A = [[a, 1], [0, 0]] the entry
+        // above the diagonal of p(A) is a^3 + c a. The identity and the swap only give c >= -5, so
+        // the cuts that lift c to 0 have to come from the local search.
+        let mut quartic = quartic();
+        quartic.set_size(2);
+        let polynomial_verifier = verifier(2, vec![DMatrix::identity(2, 2)]);
+        let result = minimize(&quartic, &config(vec![2]), &polynomial_verifier);
+        assert_eq!(result.status, Status::Converged);
+        assert_eq!(result.sample_cuts, 0);
+        assert!(result.search_cuts > 0);
+        let minimized = result.polynomial.unwrap();
+        assert!(minimized[2].abs() < 1e-4, "{}", minimized);
+        // Converged means the verifier, local search included, accepts the result.
+        assert!(polynomial_verifier.verify(&minimized).preserves);
+    }
+
+    #[test]
+    fn starting_polynomial_outside_the_cone_is_infeasible() {
+        // -x^2 + 4 fails at A = 3, and raising nothing can fix it.
+        let polynomial = Polynomial::from_vec(vec![0.0, -1.0, 4.0], 1);
+        let sample = vec![DMatrix::from_element(1, 1, 3.0)];
+        let result = minimize(&polynomial, &config(vec![0]), &verifier(1, sample));
+        assert_eq!(result.status, Status::Infeasible);
+        assert!(result.polynomial.is_none());
+    }
+}
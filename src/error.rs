@@ -0,0 +1,77 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    // The startup file or command line asked for something that can not run.
+    InvalidConfig(String),
+    // The saved state can not be resumed.
+    InvalidState(String),
+    // Text that does not parse as a polynomial.
+    InvalidPolynomial(String),
+    // A legacy results file that can not be imported.
+    InvalidImport(String),
+    // A results file this version can not read.
+    InvalidOutput(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn json(path: &Path, source: serde_json::Error) -> Self {
+        Error::Json {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Toml { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Json { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::InvalidConfig(message) => write!(f, "invalid config: {}", message),
+            Error::InvalidState(message) => write!(f, "invalid state: {}", message),
+            Error::InvalidPolynomial(message) => write!(f, "invalid polynomial: {}", message),
+            Error::InvalidImport(message) => write!(f, "can not import: {}", message),
+            Error::InvalidOutput(message) => write!(f, "invalid results: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Toml { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::InvalidConfig(_)
+            | Error::InvalidState(_)
+            | Error::InvalidPolynomial(_)
+            | Error::InvalidImport(_)
+            | Error::InvalidOutput(_) => None,
+        }
+    }
+}
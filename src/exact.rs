@@ -0,0 +1,266 @@
+// Evaluation of p(A) over any `Scalar`. With `BigRational` every step is exact: coefficients and
+// matrices can be given as rationals directly, and every finite float converts to a rational
+// without error, so p(A) is computed with no rounding at all. `certify` uses this to recheck
+// matrices that fail in floating point, see `PolynomialVerifier::confirm`.
+use crate::polynomial::Polynomial;
+use nalgebra::{ClosedAdd, ClosedMul, DMatrix};
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// The numbers a polynomial and its test matrices can be evaluated over. `f64` is the fast
+// default, `BigRational` gives exact answers.
+pub trait Scalar:
+    nalgebra::Scalar + Zero + One + ClosedAdd + ClosedMul + PartialOrd + fmt::Display
+{
+    // Exact conversion of a finite float, None for infinities and NaN.
+    fn from_f64(value: f64) -> Option<Self>;
+
+    // The nearest float.
+    fn to_f64(&self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> Option<Self> {
+        value.is_finite().then_some(value)
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+}
+
+impl Scalar for BigRational {
+    fn from_f64(value: f64) -> Option<Self> {
+        BigRational::from_float(value)
+    }
+
+    fn to_f64(&self) -> f64 {
+        ToPrimitive::to_f64(self).unwrap_or(f64::NAN)
+    }
+}
+
+// Converts a polynomial to coefficients over `S`, largest power first.
+pub fn coefficients<S: Scalar>(polynomial: &Polynomial) -> Vec<S> {
+    polynomial
+        .coefficients()
+        .iter()
+        .map(|coefficient| S::from_f64(*coefficient).expect("coefficients should be finite"))
+        .collect()
+}
+
+pub fn matrix<S: Scalar>(matrix: &DMatrix<f64>) -> DMatrix<S> {
+    matrix.map(|entry| S::from_f64(entry).expect("matrix entries should be finite"))
+}
+
+// Computes p(A) by Horner's method, coefficients largest power first.
+pub fn evaluate_matrix<S: Scalar>(coefficients: &[S], matrix: &DMatrix<S>) -> DMatrix<S> {
+    let identity = DMatrix::<S>::identity(matrix.nrows(), matrix.ncols());
+    let mut result = DMatrix::<S>::zeros(matrix.nrows(), matrix.ncols());
+    for coefficient in coefficients {
+        result = &result * matrix + &identity * coefficient.clone();
+    }
+    result
+}
+
+// The smallest entry of the matrix and its (row, column).
+pub fn min_entry<S: Scalar>(matrix: &DMatrix<S>) -> Option<(S, (usize, usize))> {
+    let mut min: Option<(S, (usize, usize))> = None;
+    for column in 0..matrix.ncols() {
+        for row in 0..matrix.nrows() {
+            let value = &matrix[(row, column)];
+            if min.as_ref().is_none_or(|(min_value, _)| value < min_value) {
+                min = Some((value.clone(), (row, column)));
+            }
+        }
+    }
+    min
+}
+
+// Every entry of p(A) is at least zero, with no tolerance.
+pub fn is_nonnegative_from_matrix<S: Scalar>(coefficients: &[S], matrix: &DMatrix<S>) -> bool {
+    let zero = S::zero();
+    evaluate_matrix(coefficients, matrix)
+        .iter()
+        .all(|value| value >= &zero)
+}
+
+// A matrix of `matrices` that p(A) has a negative entry for: its index, the smallest entry of
+// p(A) and where it is. None when p(A) is nonnegative for all of them.
+pub fn find_negative_matrix<S: Scalar>(
+    coefficients: &[S],
+    matrices: &[DMatrix<S>],
+) -> Option<(usize, S, (usize, usize))> {
+    let zero = S::zero();
+    matrices.iter().enumerate().find_map(|(index, matrix)| {
+        let (value, position) = min_entry(&evaluate_matrix(coefficients, matrix))?;
+        (value < zero).then_some((index, value, position))
+    })
+}
+
+// The exact value of the smallest entry of p(A), computed in rationals from the float
+// coefficients and matrix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    pub min_entry: String,
+    pub min_index: (usize, usize),
+    pub is_negative: bool,
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "exact smallest entry {} at row {}, column {}",
+            self.min_entry, self.min_index.0, self.min_index.1
+        )
+    }
+}
+
+pub fn certify(polynomial: &Polynomial, matrix: &DMatrix<f64>) -> Certificate {
+    let result = evaluate_matrix(
+        &coefficients::<BigRational>(polynomial),
+        &self::matrix::<BigRational>(matrix),
+    );
+    let (min_entry, min_index) =
+        min_entry(&result).unwrap_or_else(|| (BigRational::zero(), (0, 0)));
+    Certificate {
+        is_negative: min_entry < BigRational::zero(),
+        min_entry: min_entry.to_string(),
+        min_index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn evaluates_without_rounding() {
+        // 0.5 and 0.25 are exact floats, p(A) = A^2 - A + 0.25 I is (A - 0.5 I)^2.
+        let polynomial = Polynomial::from_vec(vec![1.0, -1.0, 0.25], 2);
+        let a = DMatrix::from_row_slice(2, 2, &[0.5, 1.0, 0.0, 0.5]);
+        let result = evaluate_matrix(
+            &coefficients::<BigRational>(&polynomial),
+            &matrix::<BigRational>(&a),
+        );
+        assert!(result.iter().all(|entry| entry.is_zero()));
+    }
+
+    #[test]
+    fn certifies_a_counterexample() {
+        // x^4 - 2x^2 + 1 decreases from 0 to 1/2, which makes the entry above the diagonal
+        // (p(1/2) - p(0)) / (1/2) = -7/8.
+        let polynomial = Polynomial::from_vec(vec![1.0, 0.0, -2.0, 0.0, 1.0], 2);
+        let a = DMatrix::from_row_slice(2, 2, &[0.5, 1.0, 0.0, 0.0]);
+        let certificate = certify(&polynomial, &a);
+        assert!(certificate.is_negative);
+        assert_eq!(certificate.min_index, (0, 1));
+        let expected = BigRational::new(BigInt::from(-7), BigInt::from(8));
+        assert_eq!(certificate.min_entry, expected.to_string());
+    }
+
+    #[test]
+    fn clears_a_rounding_error() {
+        // (x - a)^2 (x + b) with coefficients rounded to floats. At A = a the float sum is
+        // slightly negative while the exact value is positive.
+        let (a, b) = (0.56, 2.14);
+        let polynomial =
+            Polynomial::from_vec(vec![1.0, b - 2.0 * a, a * a - 2.0 * a * b, a * a * b], 1);
+        let matrix = DMatrix::from_element(1, 1, a);
+        let float_value: f64 = (0..4)
+            .map(|power| polynomial[3 - power] * matrix.pow(power as u32)[(0, 0)])
+            .sum();
+        assert!(float_value < 0.0);
+        let certificate = certify(&polynomial, &matrix);
+        assert!(!certificate.is_negative);
+    }
+
+    fn rational(numerator: i64, denominator: i64) -> BigRational {
+        BigRational::new(BigInt::from(numerator), BigInt::from(denominator))
+    }
+
+    #[test]
+    fn checks_rational_inputs() {
+        // (x - 1/3)^2 = x^2 - 2/3 x + 1/9 has no float coefficients. p(A) is exactly zero at
+        // A = 1/3, and at the Jordan block [[1/3, 1], [0, 1/3]] the entry above the diagonal is
+        // p'(1/3) = 0, so both are nonnegative.
+        let coefficients = vec![rational(1, 1), rational(-2, 3), rational(1, 9)];
+        let third = DMatrix::from_element(1, 1, rational(1, 3));
+        assert!(evaluate_matrix(&coefficients, &third)[(0, 0)].is_zero());
+        let jordan = DMatrix::from_row_slice(
+            2,
+            2,
+            &[
+                rational(1, 3),
+                rational(1, 1),
+                rational(0, 1),
+                rational(1, 3),
+            ],
+        );
+        assert!(is_nonnegative_from_matrix(&coefficients, &jordan));
+
+        // At [[0, 1], [0, 2/3]] the entry above the diagonal is (p(2/3) - p(0)) / (2/3) = 0, and
+        // at [[0, 1], [0, 1/2]] it is -1/6.
+        let matrices = vec![
+            third,
+            DMatrix::from_row_slice(
+                2,
+                2,
+                &[
+                    rational(0, 1),
+                    rational(1, 1),
+                    rational(0, 1),
+                    rational(2, 3),
+                ],
+            ),
+            DMatrix::from_row_slice(
+                2,
+                2,
+                &[
+                    rational(0, 1),
+                    rational(1, 1),
+                    rational(0, 1),
+                    rational(1, 2),
+                ],
+            ),
+        ];
+        let (index, value, position) = find_negative_matrix(&coefficients, &matrices).unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(value, rational(-1, 6));
+        assert_eq!(position, (0, 1));
+        assert!(find_negative_matrix(&coefficients, &matrices[..2]).is_none());
+    }
+
+    #[test]
+    fn float_and_rational_backends_agree() {
+        let polynomial = Polynomial::from_vec(vec![1.0, 0.0, -2.0, 0.0, 1.0], 2);
+        let matrices = [
+            DMatrix::from_row_slice(2, 2, &[0.5, 1.0, 0.0, 0.0]),
+            DMatrix::from_row_slice(2, 2, &[2.0, 1.0, 1.0, 2.0]),
+            DMatrix::identity(2, 2),
+        ];
+        let float_matrices: Vec<DMatrix<f64>> = matrices.iter().map(matrix::<f64>).collect();
+        let rational_matrices: Vec<DMatrix<BigRational>> =
+            matrices.iter().map(matrix::<BigRational>).collect();
+        let float = find_negative_matrix(&coefficients::<f64>(&polynomial), &float_matrices);
+        let exact = find_negative_matrix(
+            &coefficients::<BigRational>(&polynomial),
+            &rational_matrices,
+        );
+        let (float, exact) = (float.unwrap(), exact.unwrap());
+        assert_eq!(float.0, exact.0);
+        assert_eq!(float.1, Scalar::to_f64(&exact.1));
+        assert_eq!(float.2, exact.2);
+        assert_eq!(
+            polynomial.evaluate_matrix(&matrices[1]),
+            evaluate_matrix(&coefficients::<f64>(&polynomial), &float_matrices[1])
+        );
+
+        assert_eq!(<f64 as Scalar>::from_f64(f64::NAN), None);
+        assert_eq!(BigRational::from_f64(f64::INFINITY), None);
+        assert_eq!(BigRational::from_f64(0.375), Some(rational(3, 8)));
+    }
+}
@@ -0,0 +1,360 @@
+// Writes results files as JSON, CSV or a compact binary format. Only JSON keeps the run metadata,
+// the other formats hold one row per polynomial for notebooks and spreadsheets.
+use crate::error::{Error, Result};
+use crate::output::Output;
+use crate::polynomial::Polynomial;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// First bytes of a binary results file, followed by `BINARY_VERSION`.
+pub const BINARY_MAGIC: [u8; 4] = *b"MPAP";
+pub const BINARY_VERSION: u32 = 1;
+
+// Start of the header of a CSV results file, the first column is `x^<degree>` or, when there are
+// no terms, `matrix_size`.
+pub const CSV_HEADER_STARTS: [&str; 2] = ["x^", "matrix_size,"];
+
+// Bytes before the first polynomial of a binary results file.
+const BINARY_HEADER_LENGTH: u64 = 20;
+
+// Written in the generation field of the binary format when it is unknown.
+const UNKNOWN_GENERATION: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Csv,
+    Binary,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Binary => "binary",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "binary" => Ok(OutputFormat::Binary),
+            _ => Err(Error::InvalidConfig(format!(
+                "output_format must be \"json\", \"csv\" or \"binary\", not \"{}\"",
+                name
+            ))),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Binary => "bin",
+        }
+    }
+}
+
+pub fn write_output(path: &Path, output: &Output, format: OutputFormat) -> Result<()> {
+    let result = match format {
+        OutputFormat::Json => {
+            let json_object =
+                serde_json::to_string(output).map_err(|source| Error::json(path, source))?;
+            return fs::write(path, json_object).map_err(|source| Error::io(path, source));
+        }
+        OutputFormat::Csv => create(path).and_then(|file| write_csv(file, output)),
+        OutputFormat::Binary => {
+            let fields = binary_fields(path, output)?;
+            create(path).and_then(|file| write_binary(file, output, &fields))
+        }
+    };
+    result.map_err(|source| Error::io(path, source))
+}
+
+fn create(path: &Path) -> io::Result<BufWriter<File>> {
+    File::create(path).map(BufWriter::new)
+}
+
+// The most terms of any polynomial, shorter ones are padded with leading zeros.
+fn terms(output: &Output) -> usize {
+    output
+        .interesting_polynomials
+        .iter()
+        .map(Polynomial::len)
+        .max()
+        .unwrap_or(0)
+}
+
+fn padded(polynomial: &Polynomial, terms: usize) -> impl Iterator<Item = f64> + '_ {
+    std::iter::repeat_n(0.0, terms - polynomial.len())
+        .chain(polynomial.coefficients().iter().copied())
+}
+
+// The power of the coefficient picked by `better`, or None for an empty polynomial.
+fn power_of<F: Fn(f64, f64) -> bool>(polynomial: &Polynomial, better: F) -> Option<usize> {
+    let coefficients = polynomial.coefficients();
+    let mut best: Option<usize> = None;
+    for (i, coefficient) in coefficients.iter().enumerate() {
+        if best.is_none_or(|best| better(*coefficient, coefficients[best])) {
+            best = Some(i);
+        }
+    }
+    best.map(|i| coefficients.len() - 1 - i)
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// One row per polynomial with a column per power, largest first, followed by:
+// `matrix_size`, `normalizing_power` the power of the largest coefficient in absolute value,
+// `most_negative_power` the power of the smallest coefficient when it is negative,
+// `negative_terms` the number of negative coefficients, and the `generation` and lowered
+// `combination` (indices separated by `;`) when known.
+fn write_csv<W: Write>(mut writer: W, output: &Output) -> io::Result<()> {
+    let terms = terms(output);
+    let mut header: Vec<String> = (0..terms)
+        .rev()
+        .map(|power| format!("x^{}", power))
+        .collect();
+    header.extend(
+        [
+            "matrix_size",
+            "normalizing_power",
+            "most_negative_power",
+            "negative_terms",
+            "generation",
+            "combination",
+        ]
+        .iter()
+        .map(|column| column.to_string()),
+    );
+    writeln!(writer, "{}", header.join(","))?;
+
+    for (i, polynomial) in output.interesting_polynomials.iter().enumerate() {
+        let provenance = output.provenance.get(i).and_then(Option::as_ref);
+        let mut row: Vec<String> = padded(polynomial, terms)
+            .map(|coefficient| coefficient.to_string())
+            .collect();
+        let most_negative = power_of(polynomial, |a, b| a < b)
+            .filter(|power| polynomial[polynomial.len() - 1 - power] < 0.0);
+        row.push(polynomial.get_size().to_string());
+        row.push(optional(power_of(polynomial, |a, b| a.abs() > b.abs())));
+        row.push(optional(most_negative));
+        row.push(
+            polynomial
+                .coefficients()
+                .iter()
+                .filter(|coefficient| **coefficient < 0.0)
+                .count()
+                .to_string(),
+        );
+        row.push(optional(provenance.map(|provenance| provenance.generation)));
+        row.push(optional(provenance.map(|provenance| {
+            provenance
+                .combination
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<String>>()
+                .join(";")
+        })));
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()
+}
+
+// The u32 fields of the binary format: the number of terms, then the matrix size and generation
+// of every polynomial. Fails instead of truncating values that do not fit, the generation must
+// also stay below `UNKNOWN_GENERATION`.
+struct BinaryFields {
+    terms: u32,
+    polynomials: Vec<(u32, u32)>,
+}
+
+fn binary_fields(path: &Path, output: &Output) -> Result<BinaryFields> {
+    let too_large = |name: &str, value: usize| {
+        Error::InvalidOutput(format!(
+            "cannot write {} in the binary format: {} {} does not fit in a u32",
+            path.display(),
+            name,
+            value
+        ))
+    };
+    let terms = terms(output);
+    let terms = u32::try_from(terms).map_err(|_| too_large("number of terms", terms))?;
+    let mut polynomials = Vec::with_capacity(output.interesting_polynomials.len());
+    for (i, polynomial) in output.interesting_polynomials.iter().enumerate() {
+        let matrix_size = polynomial.get_size();
+        let matrix_size =
+            u32::try_from(matrix_size).map_err(|_| too_large("matrix size", matrix_size))?;
+        let generation = match output.provenance.get(i).and_then(Option::as_ref) {
+            Some(provenance) => u32::try_from(provenance.generation)
+                .ok()
+                .filter(|generation| *generation != UNKNOWN_GENERATION)
+                .ok_or_else(|| too_large("generation", provenance.generation))?,
+            None => UNKNOWN_GENERATION,
+        };
+        polynomials.push((matrix_size, generation));
+    }
+    Ok(BinaryFields { terms, polynomials })
+}
+
+// Little endian throughout: the magic bytes, the format version as u32, the number of
+// polynomials as u64 and the number of terms as u32. Then for every polynomial its matrix size
+// and generation as u32, u32::MAX when unknown, and its coefficients as f64, largest power first
+// and padded with leading zeros.
+fn write_binary<W: Write>(mut writer: W, output: &Output, fields: &BinaryFields) -> io::Result<()> {
+    let terms = fields.terms as usize;
+    writer.write_all(&BINARY_MAGIC)?;
+    writer.write_all(&BINARY_VERSION.to_le_bytes())?;
+    writer.write_all(&(output.interesting_polynomials.len() as u64).to_le_bytes())?;
+    writer.write_all(&fields.terms.to_le_bytes())?;
+    for (polynomial, (matrix_size, generation)) in output
+        .interesting_polynomials
+        .iter()
+        .zip(&fields.polynomials)
+    {
+        writer.write_all(&matrix_size.to_le_bytes())?;
+        writer.write_all(&generation.to_le_bytes())?;
+        for coefficient in padded(polynomial, terms) {
+            writer.write_all(&coefficient.to_le_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+// Reads the polynomials and generations back from a binary results file.
+pub fn read_binary(path: &Path) -> Result<Vec<(Polynomial, Option<usize>)>> {
+    let file = File::open(path).map_err(|source| Error::io(path, source))?;
+    let length = file
+        .metadata()
+        .map_err(|source| Error::io(path, source))?
+        .len();
+    let mut reader = BufReader::new(file);
+    let invalid = |message: &str| {
+        Error::InvalidOutput(format!(
+            "{} is not a binary results file: {}",
+            path.display(),
+            message
+        ))
+    };
+    let mut read = |bytes: &mut [u8]| {
+        reader
+            .read_exact(bytes)
+            .map_err(|source| Error::io(path, source))
+    };
+
+    let mut magic = [0u8; 4];
+    read(&mut magic)?;
+    if magic != BINARY_MAGIC {
+        return Err(invalid("wrong magic bytes"));
+    }
+    let mut word = [0u8; 4];
+    let mut long = [0u8; 8];
+    read(&mut word)?;
+    if u32::from_le_bytes(word) > BINARY_VERSION {
+        return Err(invalid("newer format version"));
+    }
+    read(&mut long)?;
+    let count = u64::from_le_bytes(long);
+    read(&mut word)?;
+    let terms = u32::from_le_bytes(word) as usize;
+    // Checked against the length of the file before anything is allocated, so a damaged header
+    // cannot ask for more memory than the file holds.
+    let body = (8 + 8 * terms as u64).checked_mul(count);
+    if body.is_none_or(|body| body > length.saturating_sub(BINARY_HEADER_LENGTH)) {
+        return Err(invalid(&format!(
+            "{} polynomials of {} terms do not fit in {} bytes",
+            count, terms, length
+        )));
+    }
+
+    let mut polynomials = Vec::new();
+    for _ in 0..count {
+        read(&mut word)?;
+        let matrix_size = u32::from_le_bytes(word) as usize;
+        read(&mut word)?;
+        let generation = u32::from_le_bytes(word);
+        let mut coefficients = Vec::with_capacity(terms);
+        for _ in 0..terms {
+            read(&mut long)?;
+            coefficients.push(f64::from_le_bytes(long));
+        }
+        let generation = (generation != UNKNOWN_GENERATION).then_some(generation as usize);
+        polynomials.push((Polynomial::from_vec(coefficients, matrix_size), generation));
+    }
+    Ok(polynomials)
+}
+
+// Reads the polynomials and generations back from a CSV results file.
+pub fn read_csv(path: &Path) -> Result<Vec<(Polynomial, Option<usize>)>> {
+    let file = File::open(path).map_err(|source| Error::io(path, source))?;
+    let invalid = |line: usize, message: &str| {
+        Error::InvalidOutput(format!(
+            "{} line {} is not a CSV results row: {}",
+            path.display(),
+            line,
+            message
+        ))
+    };
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next() {
+        Some(header) => header.map_err(|source| Error::io(path, source))?,
+        None => return Err(invalid(1, "no header")),
+    };
+    let terms = header
+        .split(',')
+        .take_while(|column| column.starts_with("x^"))
+        .count();
+    if header.split(',').nth(terms) != Some("matrix_size") {
+        return Err(invalid(1, "no matrix_size column after the coefficients"));
+    }
+    // The coefficients, `matrix_size`, three derived columns and `generation`.
+    let columns = terms + 5;
+
+    let mut polynomials = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(|source| Error::io(path, source))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row: Vec<&str> = line.split(',').collect();
+        if row.len() < columns {
+            return Err(invalid(i + 2, "too few columns"));
+        }
+        let coefficients = row[..terms]
+            .iter()
+            .map(|coefficient| coefficient.parse::<f64>())
+            .collect::<std::result::Result<Vec<f64>, _>>()
+            .map_err(|_| invalid(i + 2, "a coefficient is not a number"))?;
+        let matrix_size = row[terms]
+            .parse()
+            .map_err(|_| invalid(i + 2, "matrix_size is not a number"))?;
+        let generation = match row[terms + 4] {
+            "" => None,
+            generation => Some(
+                generation
+                    .parse()
+                    .map_err(|_| invalid(i + 2, "generation is not a number"))?,
+            ),
+        };
+        polynomials.push((Polynomial::from_vec(coefficients, matrix_size), generation));
+    }
+    Ok(polynomials)
+}
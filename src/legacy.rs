@@ -0,0 +1,189 @@
+// Reads the plain text results of older versions, one polynomial per line in the `Display`
+// format. Those runs only recorded their parameters in the file name, e.g.
+// `2x2matrices_5term_polynomial_100polynomial_mutations_1000000fuzzed`.
+use crate::error::{Error, Result};
+use crate::output::{Output, RunMetadata};
+use crate::polynomial::Polynomial;
+use crate::tolerance::Tolerance;
+use std::fs;
+use std::path::Path;
+
+// Reads the run parameters from a legacy file name.
+pub fn parse_file_name(name: &str) -> Result<RunMetadata> {
+    let invalid = || {
+        Error::InvalidImport(format!(
+            "\"{}\" is not named like 2x2matrices_5term_polynomial_100polynomial_mutations_1000000fuzzed",
+            name
+        ))
+    };
+    let number = |token: &str, suffix: &str| -> Result<usize> {
+        token
+            .strip_suffix(suffix)
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(invalid)
+    };
+
+    let tokens: Vec<&str> = name.split('_').collect();
+    if tokens.len() != 6 || tokens[2] != "polynomial" || tokens[4] != "mutations" {
+        return Err(invalid());
+    }
+    let size = tokens[0].strip_suffix("matrices").ok_or_else(invalid)?;
+    let (rows, columns) = size.split_once('x').ok_or_else(invalid)?;
+    let matrix_size: usize = rows.parse().map_err(|_| invalid())?;
+    if columns.parse::<usize>().ok() != Some(matrix_size) {
+        return Err(invalid());
+    }
+    Ok(RunMetadata {
+        matrix_size,
+        polynomial_length: number(tokens[1], "term")?,
+        mutated_polynomials_to_evaluate: number(tokens[3], "polynomial")?,
+        matrices_to_fuzz: number(tokens[5], "fuzzed")?,
+        imported_from: None,
+    })
+}
+
+// Loads a legacy results file. Every polynomial gets the matrix size and number of terms from the
+// file name. The tolerance is the default, which is what those versions used.
+pub fn import(path: &Path) -> Result<Output> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let mut run = parse_file_name(name)?;
+    run.imported_from = Some(path.to_path_buf());
+    let contents = fs::read_to_string(path).map_err(|source| Error::io(path, source))?;
+
+    let mut interesting_polynomials = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let polynomial = Polynomial::parse(line, run.matrix_size).map_err(|error| {
+            Error::InvalidImport(format!("{} line {}: {}", path.display(), number + 1, error))
+        })?;
+        if polynomial.len() > run.polynomial_length {
+            return Err(Error::InvalidImport(format!(
+                "{} line {} has {} terms but the file name says {}",
+                path.display(),
+                number + 1,
+                polynomial.len(),
+                run.polynomial_length
+            )));
+        }
+        let mut coefficients = vec![0.0; run.polynomial_length - polynomial.len()];
+        coefficients.extend_from_slice(polynomial.coefficients());
+        interesting_polynomials.push(Polynomial::from_vec(coefficients, run.matrix_size));
+    }
+
+    let mut output = Output::new(interesting_polynomials, Tolerance::default());
+    output.run = Some(run);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{self, OutputFormat};
+    use std::path::PathBuf;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "matrix_polynomial_analysis-legacy-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn parses_file_names() {
+        let run =
+            parse_file_name("3x3matrices_9term_polynomial_50polynomial_mutations_100000fuzzed")
+                .unwrap();
+        assert_eq!(
+            run,
+            RunMetadata {
+                matrix_size: 3,
+                polynomial_length: 9,
+                mutated_polynomials_to_evaluate: 50,
+                matrices_to_fuzz: 100000,
+                imported_from: None,
+            }
+        );
+
+        for name in [
+            "",
+            "output.json",
+            "2x3matrices_5term_polynomial_100polynomial_mutations_1000000fuzzed",
+            "2matrices_5term_polynomial_100polynomial_mutations_1000000fuzzed",
+            "2x2matrices_fiveterm_polynomial_100polynomial_mutations_1000000fuzzed",
+            "2x2matrices_5term_polynomial_100polynomial_mutations_1000000",
+            "2x2matrices_5term_polynomials_100polynomial_mutations_1000000fuzzed",
+            "2x2matrices_5term_polynomial_100polynomial_mutations_1000000fuzzed_copy",
+        ] {
+            assert!(
+                matches!(parse_file_name(name), Err(Error::InvalidImport(_))),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn pads_polynomials_to_the_file_name() {
+        let directory = directory("padding");
+        let path = directory.join("2x2matrices_4term_polynomial_10polynomial_mutations_1000fuzzed");
+        fs::write(&path, "+ 1.0000000x^2 - 0.5000000x^1 + 1.0000000x^0\n\n").unwrap();
+        let output = import(&path).unwrap();
+        assert_eq!(output.interesting_polynomials.len(), 1);
+        let polynomial = &output.interesting_polynomials[0];
+        assert_eq!(polynomial.coefficients(), &[0.0, 1.0, -0.5, 1.0]);
+        assert_eq!(polynomial.get_size(), 2);
+        let run = output.run.unwrap();
+        assert_eq!(run.matrix_size, 2);
+        assert_eq!(run.imported_from, Some(path.clone()));
+        assert_eq!(output.seed, None);
+
+        let path = directory.join("2x2matrices_2term_polynomial_10polynomial_mutations_1000fuzzed");
+        fs::write(&path, "+ 1.0000000x^2 - 0.5000000x^1 + 1.0000000x^0\n").unwrap();
+        assert!(matches!(import(&path), Err(Error::InvalidImport(_))));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn imports_a_results_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("results/3x3matrices_9term_polynomial_50polynomial_mutations_100000fuzzed");
+        let output = import(&path).unwrap();
+        let lines = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+        assert_eq!(output.interesting_polynomials.len(), lines);
+        assert!(output
+            .interesting_polynomials
+            .iter()
+            .all(|polynomial| polynomial.len() == 9 && polynomial.get_size() == 3));
+        assert_eq!(
+            output.interesting_polynomials[0].coefficients(),
+            &[0.0, 0.0, 1.0, 0.0, -0.800895, 0.0, 0.1934239, 0.0, 0.8947493]
+        );
+
+        let directory = directory("import");
+        let written = directory.join("output.json");
+        export::write_output(&written, &output, OutputFormat::Json).unwrap();
+        let loaded = Output::load(&written).unwrap();
+        assert_eq!(loaded.run, output.run);
+        let coefficients = |output: &Output| -> Vec<Vec<f64>> {
+            output
+                .interesting_polynomials
+                .iter()
+                .map(|polynomial| polynomial.coefficients().to_vec())
+                .collect()
+        };
+        assert_eq!(coefficients(&loaded), coefficients(&output));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,29 +1,51 @@
+use config::Config;
 use current_state::CurrentState;
+use error::{Error, Result};
 use itertools::Itertools;
 use log::{debug, info, warn};
 use polynomial::Polynomial;
+use polynomial_verifier::{PolynomialVerifier, VerifierConfig};
+use progress::{Progress, ProgressConfig};
 use rand::prelude::Rng;
-use rand::{seq::IteratorRandom, thread_rng};
-use std::sync::mpsc::channel;
-use std::sync::mpsc::{Receiver, Sender};
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use std::path::Path;
 use std::sync::Arc;
-use threadpool::ThreadPool;
+use std::time::Instant;
 
+pub mod config;
 pub mod current_state;
+pub mod cutting_plane;
+pub mod error;
+pub mod exact;
+pub mod export;
+pub mod legacy;
+pub mod linear_program;
+pub mod matrix_family;
+pub mod matrix_generator;
+pub mod matrix_search;
+pub mod output;
 pub mod polynomial;
 pub mod polynomial_verifier;
-pub mod matrix_generator;
+pub mod power_table;
+pub mod progress;
+pub mod run_directory;
+pub mod shutdown;
+pub mod tolerance;
+pub mod zero_pattern;
 
-fn generate_mutated_polynomials(
+fn generate_mutated_polynomials<R: Rng>(
     base_polynomial: &Polynomial,
     mutated_polynomials_to_evaluate: usize,
+    rng: &mut R,
 ) -> Vec<Polynomial> {
     info!(
         "Start generating mutated polynomials for {}",
-        base_polynomial.to_string()
+        base_polynomial
     );
     let mut mutated_polynomials = Vec::new();
-    let mut rng = rand::thread_rng();
     for i in 1..base_polynomial.len() {
         let combinations_of_i = (0..base_polynomial.len()).combinations(i);
         for combination in combinations_of_i {
@@ -61,11 +83,10 @@ fn generate_mutated_polynomials(
         mutated_polynomials.len()
     );
     if mutated_polynomials.len() > mutated_polynomials_to_evaluate {
-        let mut rng = thread_rng();
         let mut vec = Vec::new();
         for entry in mutated_polynomials
             .iter()
-            .choose_multiple(&mut rng, mutated_polynomials_to_evaluate)
+            .choose_multiple(rng, mutated_polynomials_to_evaluate)
         {
             vec.push(entry.clone());
         }
@@ -75,25 +96,27 @@ fn generate_mutated_polynomials(
     }
 }
 
-pub fn initialize_current_state(
+pub fn initialize_current_state<R: Rng>(
     base_polynomial: &Polynomial,
     mutated_polynomials_to_evaluate: usize,
+    seed: u64,
+    rng: &mut R,
 ) -> CurrentState {
-    let mut current_state = CurrentState::new(base_polynomial.len(), 0);
+    let mut current_state = CurrentState::new(base_polynomial.len(), 0, seed);
 
     current_state.starting_mutated_polynomials = vec![base_polynomial.clone()];
 
     current_state.starting_mutated_polynomials =
-        generate_mutated_polynomials(&base_polynomial, mutated_polynomials_to_evaluate);
-    
+        generate_mutated_polynomials(base_polynomial, mutated_polynomials_to_evaluate, rng);
+
     debug!("Generated mutated polynomials:");
     for poly in &current_state.starting_mutated_polynomials {
-        debug!("{}", poly.to_string());
+        debug!("{}", poly);
     }
     current_state
 }
 
-pub fn print_finished_combination(combination: &Vec<usize>) {
+pub fn print_finished_combination(combination: &[usize]) {
     let mut combo_string = String::new();
     for combo in combination {
         combo_string = format!("{} {} ", combo_string, combo);
@@ -104,45 +127,139 @@ pub fn print_finished_combination(combination: &Vec<usize>) {
     );
 }
 
+// Builds the verifier for a run. The matrices are always drawn first from the run's seed so a
+// resumed run regenerates the same matrices.
+pub fn generate_polynomial_verifier(
+    verifier_config: &VerifierConfig,
+    matrix_size: usize,
+    polynomial_length: usize,
+) -> (PolynomialVerifier, StdRng) {
+    info!(
+        "Starting to generate matrices to fuzz with seed {}",
+        verifier_config.seed
+    );
+    let mut rng = StdRng::seed_from_u64(verifier_config.seed);
+    let polynomial_verifier =
+        PolynomialVerifier::new(verifier_config, matrix_size, polynomial_length, &mut rng);
+    (polynomial_verifier, rng)
+}
+
+// Starts a run from `base_polynomial` with the settings of `config`. The config and seed are
+// saved in the state, with the fuzzed matrices when `checkpoint.store_matrices` is set, so the run
+// can be resumed exactly.
 pub fn mutate_polynomial_from_beginning(
     base_polynomial: Polynomial,
-    matrices_to_fuzz: usize,
-    mutated_polynomials_to_evaluate: usize,
-    generations: usize,
-) -> Vec<Polynomial> {
-    let current_state = initialize_current_state(&base_polynomial, mutated_polynomials_to_evaluate);
-    mutate_polynomial(current_state, matrices_to_fuzz, generations)
+    config: &Config,
+    seed: u64,
+    state_path: &Path,
+) -> Result<CurrentState> {
+    let (polynomial_verifier, mut rng) = generate_polynomial_verifier(
+        &config.verifier_config(seed)?,
+        base_polynomial.get_size(),
+        base_polynomial.len(),
+    );
+    let mut current_state = initialize_current_state(
+        &base_polynomial,
+        config.mutated_polynomials_to_evaluate,
+        seed,
+        &mut rng,
+    );
+    current_state.set_state_path(state_path);
+    current_state.set_checkpoint_config(&config.checkpoint);
+    let mut run_config = config.clone();
+    run_config.seed = Some(seed);
+    current_state.config = Some(run_config);
+    if config.checkpoint.store_matrices {
+        current_state.matrices = Some(polynomial_verifier.fuzzed_matrices());
+    }
+    mutate_polynomial_with_verifier(
+        current_state,
+        Arc::new(polynomial_verifier),
+        config.number_of_generations,
+        &config.progress,
+    )
 }
 
+// `verifier_config` should carry the seed stored in `current_state` so a resumed run regenerates
+// the same matrices. Matrices stored in the state are used as they are.
 pub fn mutate_polynomial(
-    mut current_state: CurrentState,
-    matrices_to_fuzz: usize,
+    current_state: CurrentState,
+    verifier_config: &VerifierConfig,
     generations: usize,
-) -> Vec<Polynomial> {
-    info!("Starting to generate matrices to fuzz");
-    let polynomial_verifier = Arc::new(polynomial_verifier::PolynomialVerifier::new(
-        matrices_to_fuzz,
-        current_state.starting_mutated_polynomials[0].get_size(),
-        current_state.starting_mutated_polynomials[0].len(),
-    ));
+    progress: &ProgressConfig,
+) -> Result<CurrentState> {
+    let first = current_state
+        .starting_mutated_polynomials
+        .first()
+        .ok_or_else(|| Error::InvalidState(String::from("there are no polynomials to mutate")))?;
+    let polynomial_verifier = match &current_state.matrices {
+        Some(matrices) => {
+            PolynomialVerifier::from_fuzzed_matrices(verifier_config, matrices, first.len())?
+        }
+        None => generate_polynomial_verifier(verifier_config, first.get_size(), first.len()).0,
+    };
+    mutate_polynomial_with_verifier(
+        current_state,
+        Arc::new(polynomial_verifier),
+        generations,
+        progress,
+    )
+}
 
+pub fn mutate_polynomial_with_verifier(
+    mut current_state: CurrentState,
+    polynomial_verifier: Arc<PolynomialVerifier>,
+    generations: usize,
+    progress: &ProgressConfig,
+) -> Result<CurrentState> {
+    let mut last_save = Instant::now();
+    let mut progress = Progress::new(progress)?;
     for gen in current_state.current_generation..generations {
         info!("Starting to mutate coefficients for generation {}", gen);
+        progress.start_generation(
+            &current_state,
+            generations,
+            polynomial_verifier.verifications(),
+        );
         let mut count = 0;
         for i in current_state.combinations_left.clone().iter() {
             for combination in i {
                 if combination.is_empty() {
                     continue;
                 }
-                current_state
-                    .interesting_polynomials
-                    .append(&mut mutate_coefficients(
-                        &current_state.starting_mutated_polynomials,
-                        &combination,
+                if shutdown::requested() {
+                    return interrupt(
+                        current_state,
+                        last_save,
+                        &mut progress,
                         &polynomial_verifier,
-                    ));
-                print_finished_combination(&combination);
-                current_state.remove_combination(&combination, count);
+                    );
+                }
+                let minimized = match mutate_coefficients(
+                    &current_state.starting_mutated_polynomials,
+                    combination,
+                    &polynomial_verifier,
+                ) {
+                    Some(minimized) => minimized,
+                    // The combination is left for the resumed run.
+                    None => {
+                        return interrupt(
+                            current_state,
+                            last_save,
+                            &mut progress,
+                            &polynomial_verifier,
+                        )
+                    }
+                };
+                for (parent, polynomial) in minimized {
+                    let parent = current_state.starting_mutated_polynomials[parent].clone();
+                    current_state.add_interesting_polynomial(polynomial, &parent, combination);
+                }
+                print_finished_combination(combination);
+                current_state.generation_seconds += last_save.elapsed().as_secs_f64();
+                last_save = Instant::now();
+                current_state.remove_combination(combination, count)?;
+                progress.finish_combination(&current_state, polynomial_verifier.verifications());
             }
             info!(
                 "Finished operation {} out of {}",
@@ -151,92 +268,127 @@ pub fn mutate_polynomial(
             );
             count += 1;
         }
+        progress.finish_generation(&current_state, polynomial_verifier.verifications());
         info!("Finished generation {}", gen);
-        current_state.finish_generation();
+        current_state.generation_seconds += last_save.elapsed().as_secs_f64();
+        last_save = Instant::now();
+        current_state.finish_generation()?;
     }
-    current_state.interesting_polynomials
+    Ok(current_state)
 }
 
-pub fn mutate_coefficients(
-    polynomials: &Vec<Polynomial>,
-    combination: &Vec<usize>,
-    polynomial_verifier: &Arc<polynomial_verifier::PolynomialVerifier>,
-) -> Vec<Polynomial> {
-    let pool = ThreadPool::new(num_cpus::get()); // TODO this is something that we might want control over in the startup flags.
-    let (sender, receiver): (Sender<Option<Polynomial>>, Receiver<Option<Polynomial>>) = channel();
-    let number_of_polynomials = polynomials.len();
-    let mut negative_polynomials = Vec::new();
-    for polynomial in polynomials {
-        minimize_polynomial_coefficients_async(
-            polynomial.clone(),
-            combination.clone(),
-            &pool,
-            sender.clone(),
-            polynomial_verifier.clone(),
-        );
-    }
-    for _ in 0..number_of_polynomials {
-        if let Ok(Some(message)) = receiver.recv() {
-            negative_polynomials.push(message);
-        }
-    }
-    Polynomial::collapse_polynomials(&negative_polynomials)
+// Saves the run after a signal asked it to stop, see `shutdown`.
+fn interrupt(
+    mut current_state: CurrentState,
+    last_save: Instant,
+    progress: &mut Progress,
+    polynomial_verifier: &PolynomialVerifier,
+) -> Result<CurrentState> {
+    warn!(
+        "Stopping at generation {}",
+        current_state.current_generation
+    );
+    current_state.generation_seconds += last_save.elapsed().as_secs_f64();
+    current_state.save_state()?;
+    progress.finish_generation(&current_state, polynomial_verifier.verifications());
+    Ok(current_state)
 }
 
-pub fn minimize_polynomial_coefficients_async(
-    polynomial: Polynomial,
-    combination: Vec<usize>,
-    pool: &ThreadPool,
-    sender: Sender<Option<Polynomial>>,
-    polynomial_verifier: Arc<polynomial_verifier::PolynomialVerifier>,
-) {
-    pool.execute(move || {
-        if let Err(e) = sender.send(minimize_polynomial_coefficients(
-            polynomial,
-            &combination,
-            &polynomial_verifier,
-        )) {
-            warn!("Error trying to send minimize {}", e);
-        }
-    });
+// Minimizes `combination` of every polynomial and keeps the elementwise smallest interesting
+// results, each with the index in `polynomials` of the polynomial it came from. Returns None when
+// a signal asked the run to stop before every polynomial was minimized.
+pub fn mutate_coefficients(
+    polynomials: &[Polynomial],
+    combination: &[usize],
+    polynomial_verifier: &Arc<polynomial_verifier::PolynomialVerifier>,
+) -> Option<Vec<(usize, Polynomial)>> {
+    // With fewer polynomials than workers the cores are better spent checking the matrices of one
+    // polynomial at a time. The results keep the order of `polynomials` either way. Minimizations
+    // that have not started when a signal arrives are skipped, the running ones finish.
+    let minimized_polynomials: Option<Vec<Option<Polynomial>>> =
+        if polynomials.len() < polynomial_verifier.workers() {
+            polynomials
+                .iter()
+                .map(|polynomial| {
+                    (!shutdown::requested()).then(|| {
+                        minimize_polynomial_coefficients_with(
+                            polynomial.clone(),
+                            combination,
+                            polynomial_verifier,
+                            |polynomial| polynomial_verifier.test_polynomial_parallel(polynomial),
+                        )
+                    })
+                })
+                .collect()
+        } else {
+            polynomial_verifier.pool().install(|| {
+                polynomials
+                    .par_iter()
+                    .map(|polynomial| {
+                        (!shutdown::requested()).then(|| {
+                            minimize_polynomial_coefficients(
+                                polynomial.clone(),
+                                combination,
+                                polynomial_verifier,
+                            )
+                        })
+                    })
+                    .collect()
+            })
+        };
+    let minimized_polynomials = minimized_polynomials?;
+    let (parents, negative_polynomials): (Vec<usize>, Vec<Polynomial>) = minimized_polynomials
+        .into_iter()
+        .enumerate()
+        .filter_map(|(parent, polynomial)| Some((parent, polynomial?)))
+        .unzip();
+    Some(
+        Polynomial::collapse_polynomials_indexed(&negative_polynomials)
+            .into_iter()
+            .map(|(index, polynomial)| (parents[index], polynomial))
+            .collect(),
+    )
 }
 
 pub fn minimize_polynomial_coefficients(
-    mut polynomial: Polynomial,
-    combination: &Vec<usize>,
+    polynomial: Polynomial,
+    combination: &[usize],
     polynomial_verifier: &Arc<polynomial_verifier::PolynomialVerifier>,
 ) -> Option<Polynomial> {
-    let mut backoff = 0.5;
-    let mut did_pass = false;
-    let mut old_polynomial = None;
-    while backoff > 0.001 {
-        if polynomial_verifier.test_polynomial(&polynomial) {
-            old_polynomial = Some(polynomial.clone());
-            for i in combination {
-                polynomial[i.clone()] -= backoff;
-            }
-            did_pass = true;
-        } else {
-            if did_pass {
-                backoff /= 2.0;
-            }
-            for i in combination {
-                polynomial[i.clone()] += backoff;
-            }
-            if !did_pass {
-                backoff /= 2.0;
-            }
-            did_pass = false;
-        }
+    minimize_polynomial_coefficients_with(
+        polynomial,
+        combination,
+        polynomial_verifier,
+        |polynomial| polynomial_verifier.test_polynomial(polynomial),
+    )
+}
+
+// Lowers the coefficients in `combination` together as far as the verifier's sample allows, see
+// `PolynomialVerifier::max_reduction`, and keeps the result if it is interesting.
+fn minimize_polynomial_coefficients_with<F>(
+    polynomial: Polynomial,
+    combination: &[usize],
+    polynomial_verifier: &polynomial_verifier::PolynomialVerifier,
+    test_polynomial: F,
+) -> Option<Polynomial>
+where
+    F: Fn(&Polynomial) -> bool,
+{
+    let reduction = polynomial_verifier.max_reduction(&polynomial, combination)?;
+    let mut minimized = polynomial.clone();
+    for i in combination {
+        minimized[*i] -= reduction;
     }
-    debug!(
-        "Finished minimizing coefficients for {}",
-        polynomial.to_string()
-    );
-    if let Some(polynomial) = old_polynomial {
-        if !polynomial.is_polynomial_nonnegative_with_threshold(-0.1) {
-            return Some(polynomial);
-        }
+    // The sample passes by construction, this runs the exact and local search stages when they
+    // are enabled.
+    if !test_polynomial(&minimized) {
+        debug!("Reduction of {} does not pass the verifier", polynomial);
+        return None;
+    }
+    debug!("Finished minimizing coefficients for {}", minimized);
+    let threshold = polynomial_verifier.tolerance().interesting_threshold;
+    if !minimized.is_polynomial_nonnegative_with_threshold(threshold) {
+        return Some(minimized);
     }
     None
 }
@@ -0,0 +1,571 @@
+// A small dense linear program solver: maximize c.x subject to rows a.x <= b and bounds on each
+// variable. Rows with a single nonzero coefficient are folded into the bounds as they are added, so
+// a program with few variables and millions of such rows needs no storage per row. The rest is
+// solved with the two phase dictionary simplex method using Bland's rule.
+
+// Pivots and ratios smaller than this are treated as zero. Rows are scaled so their largest
+// coefficient is one.
+const EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Solution {
+    Optimal { x: Vec<f64>, value: f64 },
+    Infeasible,
+    Unbounded,
+}
+
+#[derive(Debug, Clone)]
+pub struct LinearProgram {
+    variables: usize,
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+    // Rows of `a`, one after another.
+    rows: Vec<f64>,
+    rhs: Vec<f64>,
+    // Set when a row with no variables can not hold.
+    infeasible: bool,
+}
+
+impl LinearProgram {
+    // A program over `variables` free variables with no constraints.
+    pub fn new(variables: usize) -> Self {
+        LinearProgram {
+            variables,
+            lower: vec![f64::NEG_INFINITY; variables],
+            upper: vec![f64::INFINITY; variables],
+            rows: Vec::new(),
+            rhs: Vec::new(),
+            infeasible: false,
+        }
+    }
+
+    pub fn variables(&self) -> usize {
+        self.variables
+    }
+
+    // The rows stored for the simplex, not counting the ones folded into bounds.
+    pub fn constraints(&self) -> usize {
+        self.rhs.len()
+    }
+
+    // Narrows the bounds of `variable` to [lower, upper].
+    pub fn set_bounds(&mut self, variable: usize, lower: f64, upper: f64) {
+        self.lower[variable] = self.lower[variable].max(lower);
+        self.upper[variable] = self.upper[variable].min(upper);
+    }
+
+    // Adds the constraint a.x <= b.
+    pub fn add_constraint(&mut self, a: &[f64], b: f64) {
+        assert_eq!(
+            a.len(),
+            self.variables,
+            "constraint has the wrong number of variables"
+        );
+        let scale = a.iter().fold(0.0f64, |max, value| max.max(value.abs()));
+        if scale == 0.0 {
+            if b < -EPSILON {
+                self.infeasible = true;
+            }
+            return;
+        }
+        let mut nonzero = a.iter().enumerate().filter(|(_, value)| **value != 0.0);
+        if let (Some((variable, value)), None) = (nonzero.next(), nonzero.next()) {
+            self.add_variable_constraint(variable, *value, b);
+            return;
+        }
+        self.rows.extend(a.iter().map(|value| value / scale));
+        self.rhs.push(b / scale);
+    }
+
+    // Adds the constraint a x_variable <= b, the same as `add_constraint` with one nonzero
+    // coefficient but without building the row.
+    pub fn add_variable_constraint(&mut self, variable: usize, a: f64, b: f64) {
+        if a > 0.0 {
+            self.upper[variable] = self.upper[variable].min(b / a);
+        } else if a < 0.0 {
+            self.lower[variable] = self.lower[variable].max(b / a);
+        } else if b < -EPSILON {
+            self.infeasible = true;
+        }
+    }
+
+    // Adds the constraint a.x >= b.
+    pub fn add_lower_constraint(&mut self, a: &[f64], b: f64) {
+        let negated: Vec<f64> = a.iter().map(|value| -value).collect();
+        self.add_constraint(&negated, -b);
+    }
+
+    pub fn maximize(&self, objective: &[f64]) -> Solution {
+        assert_eq!(
+            objective.len(),
+            self.variables,
+            "objective has the wrong number of variables"
+        );
+        if self.infeasible || (0..self.variables).any(|v| self.lower[v] > self.upper[v]) {
+            return Solution::Infeasible;
+        }
+        let standard = StandardForm::new(self, objective);
+        match standard.dictionary().solve() {
+            Solution::Optimal { x, value } => {
+                let x = standard.original(&x);
+                Solution::Optimal {
+                    value: value + standard.offset,
+                    x,
+                }
+            }
+            other => other,
+        }
+    }
+
+    pub fn minimize(&self, objective: &[f64]) -> Solution {
+        let negated: Vec<f64> = objective.iter().map(|value| -value).collect();
+        match self.maximize(&negated) {
+            Solution::Optimal { x, value } => Solution::Optimal { x, value: -value },
+            other => other,
+        }
+    }
+}
+
+// How an original variable is written in terms of the nonnegative standard form variables.
+#[derive(Debug, Clone, Copy)]
+enum Substitution {
+    // x = lower + y
+    Shifted(usize, f64),
+    // x = upper - y
+    Reflected(usize, f64),
+    // x = y1 - y2
+    Split(usize, usize),
+}
+
+// The program rewritten as maximize c.y subject to A y <= b, y >= 0.
+struct StandardForm {
+    substitutions: Vec<Substitution>,
+    columns: usize,
+    rows: Vec<f64>,
+    rhs: Vec<f64>,
+    objective: Vec<f64>,
+    offset: f64,
+}
+
+impl StandardForm {
+    fn new(program: &LinearProgram, objective: &[f64]) -> Self {
+        let mut substitutions = Vec::with_capacity(program.variables);
+        let mut columns = 0;
+        for v in 0..program.variables {
+            let (lower, upper) = (program.lower[v], program.upper[v]);
+            let substitution = if lower.is_finite() {
+                Substitution::Shifted(columns, lower)
+            } else if upper.is_finite() {
+                Substitution::Reflected(columns, upper)
+            } else {
+                columns += 1;
+                Substitution::Split(columns - 1, columns)
+            };
+            columns += 1;
+            substitutions.push(substitution);
+        }
+
+        let mut standard = StandardForm {
+            substitutions,
+            columns,
+            rows: Vec::new(),
+            rhs: Vec::new(),
+            objective: vec![0.0; columns],
+            offset: 0.0,
+        };
+        for (row, b) in program.rows.chunks(program.variables).zip(&program.rhs) {
+            let (row, b) = standard.substitute(row, *b);
+            standard.rows.extend(row);
+            standard.rhs.push(b);
+        }
+        // Finite upper bounds of shifted variables become rows y <= upper - lower.
+        for v in 0..program.variables {
+            if let Substitution::Shifted(column, lower) = standard.substitutions[v] {
+                if program.upper[v].is_finite() {
+                    let mut row = vec![0.0; columns];
+                    row[column] = 1.0;
+                    standard.rows.extend(row);
+                    standard.rhs.push(program.upper[v] - lower);
+                }
+            }
+        }
+        let (objective, offset) = standard.substitute(objective, 0.0);
+        standard.objective = objective;
+        standard.offset = -offset;
+        standard
+    }
+
+    // Rewrites a.x <= b in the standard form variables.
+    fn substitute(&self, a: &[f64], mut b: f64) -> (Vec<f64>, f64) {
+        let mut row = vec![0.0; self.columns];
+        for (value, substitution) in a.iter().zip(&self.substitutions) {
+            match *substitution {
+                Substitution::Shifted(column, lower) => {
+                    row[column] = *value;
+                    b -= value * lower;
+                }
+                Substitution::Reflected(column, upper) => {
+                    row[column] = -value;
+                    b -= value * upper;
+                }
+                Substitution::Split(positive, negative) => {
+                    row[positive] = *value;
+                    row[negative] = -value;
+                }
+            }
+        }
+        (row, b)
+    }
+
+    fn original(&self, y: &[f64]) -> Vec<f64> {
+        self.substitutions
+            .iter()
+            .map(|substitution| match *substitution {
+                Substitution::Shifted(column, lower) => lower + y[column],
+                Substitution::Reflected(column, upper) => upper - y[column],
+                Substitution::Split(positive, negative) => y[positive] - y[negative],
+            })
+            .collect()
+    }
+
+    fn dictionary(&self) -> Dictionary {
+        let rows = self.rhs.len();
+        Dictionary {
+            columns: self.columns,
+            basic: (self.columns..self.columns + rows).collect(),
+            nonbasic: (0..self.columns).collect(),
+            coefficients: self.rows.clone(),
+            values: self.rhs.clone(),
+            costs: self.objective.clone(),
+            objective: self.objective.clone(),
+            value: 0.0,
+        }
+    }
+}
+
+// The dictionary x_B = values - coefficients x_N, z = value + costs.x_N. Variables are numbered
+// with the standard form columns first, then one slack per row, then the phase one variable.
+struct Dictionary {
+    columns: usize,
+    basic: Vec<usize>,
+    nonbasic: Vec<usize>,
+    coefficients: Vec<f64>,
+    values: Vec<f64>,
+    costs: Vec<f64>,
+    // The phase two objective over the standard form columns.
+    objective: Vec<f64>,
+    value: f64,
+}
+
+impl Dictionary {
+    fn width(&self) -> usize {
+        self.nonbasic.len()
+    }
+
+    fn solve(mut self) -> Solution {
+        if self.values.iter().any(|value| *value < -EPSILON) && !self.phase_one() {
+            return Solution::Infeasible;
+        }
+        if !self.run() {
+            return Solution::Unbounded;
+        }
+        let mut y = vec![0.0; self.columns];
+        for (row, variable) in self.basic.iter().enumerate() {
+            if *variable < self.columns {
+                y[*variable] = self.values[row];
+            }
+        }
+        Solution::Optimal {
+            x: y,
+            value: self.value,
+        }
+    }
+
+    // Finds a feasible dictionary by maximizing -x0 with x0 added to every row. Returns false
+    // when the program is infeasible.
+    fn phase_one(&mut self) -> bool {
+        let auxiliary = self.columns + self.basic.len();
+        let width = self.width();
+        let mut coefficients = Vec::with_capacity(self.basic.len() * (width + 1));
+        for row in self.coefficients.chunks(width) {
+            coefficients.extend_from_slice(row);
+            coefficients.push(-1.0);
+        }
+        self.coefficients = coefficients;
+        self.nonbasic.push(auxiliary);
+        self.costs = vec![0.0; width];
+        self.costs.push(-1.0);
+        self.value = 0.0;
+
+        let leaving = (0..self.basic.len())
+            .min_by(|a, b| self.values[*a].total_cmp(&self.values[*b]))
+            .expect("phase one needs a row");
+        self.pivot(leaving, width);
+        self.run();
+        if self.value < -EPSILON.sqrt() {
+            return false;
+        }
+
+        // Move x0 out of the basis if it is still there at zero, then drop its column.
+        if let Some(row) = self
+            .basic
+            .iter()
+            .position(|variable| *variable == auxiliary)
+        {
+            let width = self.width();
+            let column = (0..width)
+                .filter(|column| self.coefficients[row * width + column].abs() > EPSILON)
+                .max_by(|a, b| {
+                    let a = self.coefficients[row * width + a].abs();
+                    let b = self.coefficients[row * width + b].abs();
+                    a.total_cmp(&b)
+                });
+            match column {
+                Some(column) => self.pivot(row, column),
+                None => {
+                    self.remove_row(row);
+                }
+            }
+        }
+        let column = self
+            .nonbasic
+            .iter()
+            .position(|variable| *variable == auxiliary)
+            .expect("x0 is nonbasic after phase one");
+        self.remove_column(column);
+
+        // Write the real objective in terms of the nonbasic variables.
+        let width = self.width();
+        self.value = 0.0;
+        self.costs = self
+            .nonbasic
+            .iter()
+            .map(|variable| self.objective.get(*variable).copied().unwrap_or(0.0))
+            .collect();
+        for (row, variable) in self.basic.iter().enumerate() {
+            let cost = self.objective.get(*variable).copied().unwrap_or(0.0);
+            if cost == 0.0 {
+                continue;
+            }
+            self.value += cost * self.values[row];
+            for column in 0..width {
+                self.costs[column] -= cost * self.coefficients[row * width + column];
+            }
+        }
+        true
+    }
+
+    // Pivots until optimal. Returns false when the objective is unbounded.
+    fn run(&mut self) -> bool {
+        loop {
+            let width = self.width();
+            // Bland's rule, the entering variable with the smallest index.
+            let entering = (0..width)
+                .filter(|column| self.costs[*column] > EPSILON)
+                .min_by_key(|column| self.nonbasic[*column]);
+            let column = match entering {
+                Some(column) => column,
+                None => return true,
+            };
+            let mut leaving: Option<(usize, f64)> = None;
+            for row in 0..self.basic.len() {
+                let coefficient = self.coefficients[row * width + column];
+                if coefficient <= EPSILON {
+                    continue;
+                }
+                let ratio = self.values[row].max(0.0) / coefficient;
+                let better = match leaving {
+                    None => true,
+                    Some((best, best_ratio)) => {
+                        ratio < best_ratio
+                            || (ratio == best_ratio && self.basic[row] < self.basic[best])
+                    }
+                };
+                if better {
+                    leaving = Some((row, ratio));
+                }
+            }
+            match leaving {
+                Some((row, _)) => self.pivot(row, column),
+                None => return false,
+            }
+        }
+    }
+
+    fn pivot(&mut self, row: usize, column: usize) {
+        let width = self.width();
+        let pivot = self.coefficients[row * width + column];
+        for j in 0..width {
+            if j != column {
+                self.coefficients[row * width + j] /= pivot;
+            }
+        }
+        self.coefficients[row * width + column] = 1.0 / pivot;
+        self.values[row] /= pivot;
+
+        for i in 0..self.basic.len() {
+            if i == row {
+                continue;
+            }
+            let factor = self.coefficients[i * width + column];
+            if factor == 0.0 {
+                continue;
+            }
+            for j in 0..width {
+                if j != column {
+                    self.coefficients[i * width + j] -= factor * self.coefficients[row * width + j];
+                }
+            }
+            self.coefficients[i * width + column] =
+                -factor * self.coefficients[row * width + column];
+            self.values[i] -= factor * self.values[row];
+        }
+
+        let cost = self.costs[column];
+        for j in 0..width {
+            if j != column {
+                self.costs[j] -= cost * self.coefficients[row * width + j];
+            }
+        }
+        self.costs[column] = -cost * self.coefficients[row * width + column];
+        self.value += cost * self.values[row];
+
+        std::mem::swap(&mut self.basic[row], &mut self.nonbasic[column]);
+    }
+
+    fn remove_row(&mut self, row: usize) {
+        let width = self.width();
+        self.coefficients.drain(row * width..(row + 1) * width);
+        self.values.remove(row);
+        self.basic.remove(row);
+    }
+
+    fn remove_column(&mut self, column: usize) {
+        let width = self.width();
+        let mut index = 0;
+        self.coefficients.retain(|_| {
+            let keep = index % width != column;
+            index += 1;
+            keep
+        });
+        self.costs.remove(column);
+        self.nonbasic.remove(column);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_optimal(solution: Solution, expected_x: &[f64], expected_value: f64) {
+        match solution {
+            Solution::Optimal { x, value } => {
+                assert!((value - expected_value).abs() < 1e-9, "value {}", value);
+                for (x, expected) in x.iter().zip(expected_x) {
+                    assert!((x - expected).abs() < 1e-9, "x {:?}", x);
+                }
+            }
+            other => panic!("expected an optimum, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn finds_the_optimum() {
+        // maximize 3x + 2y with x + y <= 4, x + 3y <= 6, x <= 3 and x, y >= 0.
+        let mut program = LinearProgram::new(2);
+        program.set_bounds(0, 0.0, 3.0);
+        program.set_bounds(1, 0.0, f64::INFINITY);
+        program.add_constraint(&[1.0, 1.0], 4.0);
+        program.add_constraint(&[1.0, 3.0], 6.0);
+        assert_eq!(program.constraints(), 2);
+        assert_optimal(program.maximize(&[3.0, 2.0]), &[3.0, 1.0], 11.0);
+    }
+
+    #[test]
+    fn phase_one_starts_from_an_infeasible_origin() {
+        // minimize x + y with x + y >= 2, x - y <= 1 and x, y >= 0. The origin breaks the first
+        // row, so phase one has to find a feasible point first.
+        let mut program = LinearProgram::new(2);
+        program.set_bounds(0, 0.0, f64::INFINITY);
+        program.set_bounds(1, 0.0, f64::INFINITY);
+        program.add_lower_constraint(&[1.0, 1.0], 2.0);
+        program.add_constraint(&[1.0, -1.0], 1.0);
+        match program.minimize(&[1.0, 1.0]) {
+            Solution::Optimal { x, value } => {
+                assert!((value - 2.0).abs() < 1e-9);
+                assert!(x[0] + x[1] >= 2.0 - 1e-9);
+                assert!(x[0] - x[1] <= 1.0 + 1e-9);
+            }
+            other => panic!("expected an optimum, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn free_variables_are_split() {
+        // maximize -x - y with x >= -1 - y, x <= 5, y <= 2 and both free otherwise.
+        let mut program = LinearProgram::new(2);
+        program.add_lower_constraint(&[1.0, 1.0], -1.0);
+        program.add_constraint(&[1.0, 0.0], 5.0);
+        program.add_constraint(&[0.0, 1.0], 2.0);
+        assert_eq!(program.constraints(), 1);
+        assert_optimal(program.maximize(&[-1.0, -1.0]), &[], 1.0);
+    }
+
+    #[test]
+    fn detects_infeasible_programs() {
+        let mut program = LinearProgram::new(2);
+        program.set_bounds(0, 0.0, f64::INFINITY);
+        program.set_bounds(1, 0.0, f64::INFINITY);
+        program.add_constraint(&[1.0, 1.0], 1.0);
+        program.add_lower_constraint(&[1.0, 1.0], 2.0);
+        assert_eq!(program.maximize(&[1.0, 0.0]), Solution::Infeasible);
+
+        // Contradictory bounds and an empty row that can not hold are found without pivoting.
+        let mut program = LinearProgram::new(1);
+        program.set_bounds(0, 2.0, 1.0);
+        assert_eq!(program.maximize(&[1.0]), Solution::Infeasible);
+        let mut program = LinearProgram::new(1);
+        program.add_constraint(&[0.0], -1.0);
+        assert_eq!(program.maximize(&[1.0]), Solution::Infeasible);
+    }
+
+    #[test]
+    fn detects_unbounded_programs() {
+        let mut program = LinearProgram::new(2);
+        program.set_bounds(0, 0.0, f64::INFINITY);
+        program.set_bounds(1, 0.0, f64::INFINITY);
+        program.add_constraint(&[1.0, -1.0], 1.0);
+        assert_eq!(program.maximize(&[1.0, 1.0]), Solution::Unbounded);
+        assert_eq!(
+            program.minimize(&[1.0, 1.0]),
+            Solution::Optimal {
+                x: vec![0.0, 0.0],
+                value: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn single_variable_rows_become_bounds() {
+        // 2x <= 6 and -x <= 1 only narrow the bounds of x to [-1, 3].
+        let mut program = LinearProgram::new(1);
+        program.add_constraint(&[2.0], 6.0);
+        program.add_variable_constraint(0, -1.0, 1.0);
+        assert_eq!(program.constraints(), 0);
+        assert_optimal(program.maximize(&[1.0]), &[3.0], 3.0);
+        assert_optimal(program.minimize(&[1.0]), &[-1.0], -1.0);
+    }
+
+    #[test]
+    fn degenerate_vertices_terminate() {
+        // Three rows meet at the optimum (1, 1), Bland's rule keeps the pivots from cycling.
+        let mut program = LinearProgram::new(2);
+        program.set_bounds(0, 0.0, f64::INFINITY);
+        program.set_bounds(1, 0.0, f64::INFINITY);
+        program.add_constraint(&[1.0, 0.0], 1.0);
+        program.add_constraint(&[0.0, 1.0], 1.0);
+        program.add_constraint(&[1.0, 1.0], 2.0);
+        program.add_constraint(&[2.0, 1.0], 3.0);
+        assert_optimal(program.maximize(&[1.0, 1.0]), &[1.0, 1.0], 2.0);
+    }
+}
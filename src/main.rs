@@ -1,165 +1,840 @@
-use log::{error, info};
+use clap::{Args, Parser, Subcommand};
+use log::{info, warn};
+use matrix_polynomial_analysis::config::{Config, Mode};
 use matrix_polynomial_analysis::current_state::CurrentState;
+use matrix_polynomial_analysis::cutting_plane::{self, CuttingPlaneConfig, CuttingPlaneResult};
+use matrix_polynomial_analysis::error::{Error, Result};
+use matrix_polynomial_analysis::export::{self, OutputFormat};
+use matrix_polynomial_analysis::legacy;
+use matrix_polynomial_analysis::output::{Output, RunMetadata};
 use matrix_polynomial_analysis::polynomial::Polynomial;
+use matrix_polynomial_analysis::polynomial_verifier::Verdict;
+use matrix_polynomial_analysis::run_directory::{self, RunDirectory};
+use matrix_polynomial_analysis::tolerance::Tolerance;
 use matrix_polynomial_analysis::*;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::fs::File;
-use std::time::Instant;
-use toml;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Instant, SystemTime};
 
-// Top level struct to hold the TOML data.
-#[derive(Deserialize)]
-struct Data {
-    config: Config,
+/// Search for polynomials that preserve the nonnegativity of matrices.
+#[derive(Parser)]
+#[clap(version, about)]
+struct Cli {
+    /// Startup file to load the configuration from.
+    #[clap(long, global = true, default_value = "startup.toml")]
+    config: PathBuf,
+
+    /// Where to write the results, `output.<extension>` for the output format by default. With
+    /// more than one input `import` writes to this directory, `output` by default.
+    #[clap(long, global = true)]
+    output: Option<PathBuf>,
+
+    /// Where to save and resume the run state.
+    #[clap(long, global = true, default_value = "state.json")]
+    state: PathBuf,
+
+    /// Keep the config, state, results and log of a `mutate`, `map-space` or `resume` in the
+    /// run directory `<runs>/<name>` instead of `--config`, `--output` and `--state`.
+    #[clap(long, global = true)]
+    run: Option<String>,
+
+    /// Directory holding the named runs.
+    #[clap(long, global = true, default_value = run_directory::DEFAULT_RUNS_DIRECTORY)]
+    runs: PathBuf,
+
+    #[clap(flatten)]
+    overrides: ConfigOverrides,
+
+    /// Runs the `mode` from the config file when no command is given.
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// Values that replace the ones in the config file.
+#[derive(Args)]
+struct ConfigOverrides {
+    /// Starting polynomial written out, e.g. "x^4 + x^3 - 0.5x^2 + x + 1".
+    #[clap(
+        long,
+        global = true,
+        allow_hyphen_values = true,
+        parse(try_from_str = parse_coefficients)
+    )]
+    starting_polynomial: Option<Coefficients>,
+
+    /// Size of matrices to be testing.
+    #[clap(long, global = true)]
+    matrix_size: Option<usize>,
+
+    /// Approximate number of matrices to validate polynomials at.
+    #[clap(long, global = true)]
+    matrices_to_fuzz: Option<usize>,
+
+    /// Mutations of the starting polynomial to minimize.
+    #[clap(long, global = true)]
+    mutated_polynomials_to_evaluate: Option<usize>,
+
+    /// Number of terms in the polynomials of `map-space`.
+    #[clap(long, global = true)]
+    polynomial_length: Option<usize>,
+
+    /// Times to feed the output polynomials back through the minimizer.
+    #[clap(long, global = true)]
+    number_of_generations: Option<usize>,
+
+    /// Seed for the random number generator.
+    #[clap(long, global = true)]
+    seed: Option<u64>,
+
+    /// Comma separated matrix family names.
+    #[clap(long, global = true, use_value_delimiter = true)]
+    matrix_families: Option<Vec<String>>,
+
+    /// Weighted matrices to test for every zero pattern.
+    #[clap(long, global = true)]
+    zero_pattern_samples: Option<usize>,
+
+    /// Recheck float failures in exact rational arithmetic.
+    #[clap(long, global = true)]
+    exact_arithmetic: bool,
+
+    /// Worker threads to use, 0 uses every core.
+    #[clap(long, global = true)]
+    workers: Option<usize>,
+
+    /// Finish every verification with a local search for a violating matrix.
+    #[clap(long, global = true)]
+    matrix_search: bool,
+
+    /// Random restarts of the local matrix search.
+    #[clap(long, global = true)]
+    matrix_search_restarts: Option<usize>,
+
+    /// Format of the results file: json, csv or binary.
+    #[clap(long, global = true)]
+    output_format: Option<OutputFormat>,
+
+    /// Seconds between saves of the run state, 0 saves after every combination.
+    #[clap(long, global = true)]
+    checkpoint_interval: Option<f64>,
+
+    /// Append progress reports to this file as JSON lines.
+    #[clap(long, global = true)]
+    progress_json: Option<PathBuf>,
+
+    /// Do not draw the progress line on the terminal.
+    #[clap(long, global = true)]
+    no_progress: bool,
+}
+
+// The coefficients of `--starting-polynomial`. A `Vec` field would make clap expect a list of
+// values.
+#[derive(Clone)]
+struct Coefficients(Vec<f64>);
+
+fn parse_coefficients(text: &str) -> Result<Coefficients> {
+    Polynomial::parse_coefficients(text).map(Coefficients)
+}
+
+impl ConfigOverrides {
+    fn apply(self, config: &mut Config) {
+        if let Some(Coefficients(coefficients)) = self.starting_polynomial {
+            config.starting_polynomial = coefficients;
+        }
+        if let Some(matrix_size) = self.matrix_size {
+            config.matrix_size = matrix_size;
+        }
+        if let Some(matrices_to_fuzz) = self.matrices_to_fuzz {
+            config.matrices_to_fuzz = matrices_to_fuzz;
+        }
+        if let Some(mutated_polynomials) = self.mutated_polynomials_to_evaluate {
+            config.mutated_polynomials_to_evaluate = mutated_polynomials;
+        }
+        if let Some(polynomial_length) = self.polynomial_length {
+            config.polynomial_length = polynomial_length;
+        }
+        if let Some(number_of_generations) = self.number_of_generations {
+            config.number_of_generations = number_of_generations;
+        }
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if let Some(matrix_families) = self.matrix_families {
+            config.matrix_families = matrix_families;
+        }
+        if let Some(zero_pattern_samples) = self.zero_pattern_samples {
+            config.zero_pattern_samples = zero_pattern_samples;
+        }
+        if self.exact_arithmetic {
+            config.exact_arithmetic = true;
+        }
+        if let Some(workers) = self.workers {
+            config.workers = workers;
+        }
+        if self.matrix_search {
+            config.matrix_search = true;
+        }
+        if let Some(restarts) = self.matrix_search_restarts {
+            config.matrix_search_restarts = restarts;
+        }
+        if let Some(output_format) = self.output_format {
+            config.output_format = output_format;
+        }
+        if let Some(interval) = self.checkpoint_interval {
+            config.checkpoint.interval_seconds = interval;
+        }
+        if self.progress_json.is_some() {
+            config.progress.json_lines = self.progress_json;
+        }
+        if self.no_progress {
+            config.progress.display = false;
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Test a polynomial against the fuzzed matrices (mode 1).
+    #[clap(allow_negative_numbers = true)]
+    Test {
+        /// Coefficients, largest power first. Defaults to `starting_polynomial`.
+        coefficients: Vec<f64>,
+    },
+    /// Mutate a polynomial and minimize its coefficients (mode 2).
+    #[clap(allow_negative_numbers = true)]
+    Mutate {
+        /// Coefficients, largest power first. Defaults to `starting_polynomial`.
+        coefficients: Vec<f64>,
+    },
+    /// Map out the space of polynomials with `polynomial_length` terms (mode 3).
+    MapSpace,
+    /// Resume the run saved in the state file (mode 4).
+    Resume {
+        /// Name of the run to resume, the same as `--run <name>`.
+        name: Option<String>,
+
+        /// Refuse to resume when the config differs from the one the run was started with.
+        #[clap(long)]
+        strict: bool,
+
+        /// Generations to run in total. Defaults to `number_of_generations` of the run.
+        #[clap(long)]
+        generations: Option<usize>,
+    },
+    /// List the named runs and how far each has got.
+    Runs,
+    /// Show how far a named run has got and where its files are.
+    Status {
+        /// Name of the run.
+        name: String,
+    },
+    /// Lower coefficients of a polynomial with a linear program and cutting planes.
+    #[clap(allow_negative_numbers = true)]
+    CuttingPlane {
+        /// Coefficients, largest power first. Defaults to `starting_polynomial`.
+        coefficients: Vec<f64>,
+    },
+    /// Verify every polynomial in a results file again with the current config.
+    Recheck {
+        /// A results file written by `mutate`, `map-space`, `resume` or `import`.
+        input: PathBuf,
+    },
+    /// Convert legacy text results, named like
+    /// `2x2matrices_5term_polynomial_100polynomial_mutations_1000000fuzzed`, to results files.
+    Import {
+        /// Legacy results files. With more than one, `--output` is a directory that gets a
+        /// `<file name>.json` for each.
+        #[clap(required = true)]
+        inputs: Vec<PathBuf>,
+    },
 }
 
-// Config struct holds to data from the `[config]` section.
-#[derive(Deserialize)]
-struct Config {
+// Where a run reads and writes its files.
+struct Files {
+    output: PathBuf,
+    state: PathBuf,
+}
+
+// `output.json`, `output.csv` or `output.bin`.
+fn default_output(output_format: OutputFormat) -> PathBuf {
+    PathBuf::from("output").with_extension(output_format.extension())
+}
+
+// The format `command` writes its results in. Only the polynomials found by `mutate`, `map-space`
+// and `resume` follow `output_format`, the reports of the other commands are always JSON.
+fn command_format(command: &Command, output_format: OutputFormat) -> OutputFormat {
+    match command {
+        Command::Mutate { .. } | Command::MapSpace | Command::Resume { .. } => output_format,
+        _ => OutputFormat::Json,
+    }
+}
+
+// Output of mode 1, kept so counterexamples can be reproduced.
+#[derive(Serialize, Deserialize)]
+struct TestOutput {
+    polynomial: Polynomial,
     matrix_size: usize,
-    matrices_to_fuzz: usize,
-    mutated_polynomials_to_evaluate: usize,
-    polynomial_length: usize,
-    mode: usize,
-    starting_polynomial: Vec<f64>,
-    number_of_generations: usize,
+    seed: u64,
+    tolerance: Tolerance,
+    verdict: Verdict,
 }
 
+// A polynomial from a results file together with its new verdict.
 #[derive(Serialize, Deserialize)]
-struct Output {
-    interesting_polynomials: Vec<Polynomial>,
+struct CheckedPolynomial {
+    polynomial: Polynomial,
+    verdict: Verdict,
 }
 
-enum PolynomialMode {
-    TestPolynomial,
-    MutatePolynomial,
-    MapSpace,
-    ReturnState,
-    Error,
+#[derive(Serialize, Deserialize)]
+struct RecheckOutput {
+    matrix_size: usize,
+    seed: u64,
+    tolerance: Tolerance,
+    checked_polynomials: Vec<CheckedPolynomial>,
+}
+
+// Output of the cutting plane mode.
+#[derive(Serialize, Deserialize)]
+struct CuttingPlaneOutput {
+    starting_polynomial: Polynomial,
+    matrix_size: usize,
+    seed: u64,
+    tolerance: Tolerance,
+    cutting_plane: CuttingPlaneConfig,
+    result: CuttingPlaneResult,
+}
+
+fn mode_to_command(mode: Mode) -> Command {
+    match mode {
+        Mode::Test => Command::Test {
+            coefficients: Vec::new(),
+        },
+        Mode::Mutate => Command::Mutate {
+            coefficients: Vec::new(),
+        },
+        Mode::MapSpace => Command::MapSpace,
+        Mode::Resume => Command::Resume {
+            name: None,
+            strict: false,
+            generations: None,
+        },
+        Mode::CuttingPlane => Command::CuttingPlane {
+            coefficients: Vec::new(),
+        },
+    }
 }
 
-fn i32_to_polynomial_mode(val: usize) -> PolynomialMode {
-    match val {
-        1 => PolynomialMode::TestPolynomial,
-        2 => PolynomialMode::MutatePolynomial,
-        3 => PolynomialMode::MapSpace,
-        4 => PolynomialMode::ReturnState,
-        _ => PolynomialMode::Error,
+// The mode whose requirements `command` has to satisfy, `recheck` verifies like `test`.
+fn command_mode(command: &Command) -> Mode {
+    match command {
+        Command::Test { .. } | Command::Recheck { .. } => Mode::Test,
+        Command::Mutate { .. } => Mode::Mutate,
+        Command::MapSpace => Mode::MapSpace,
+        Command::Resume { .. } => Mode::Resume,
+        Command::CuttingPlane { .. } => Mode::CuttingPlane,
+        Command::Import { .. } | Command::Runs | Command::Status { .. } => {
+            unreachable!("runs without a config")
+        }
     }
 }
 
-fn read_user_from_file() -> Config {
-    let file_contents = fs::read_to_string("startup.toml").expect("file should open read only");
-    let data: Data = toml::from_str(&file_contents).expect("Unable to load data");
-    data.config
+fn starting_polynomial(args: &Config) -> Polynomial {
+    Polynomial::from_vec(args.starting_polynomial.clone(), args.matrix_size)
 }
 
-fn print_polynomials(polynomials: Vec<Polynomial>) {
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let json_object = serde_json::to_string(value).map_err(|source| Error::json(path, source))?;
+    fs::write(path, json_object).map_err(|source| Error::io(path, source))
+}
+
+// The parameters of a mutate or map-space run over polynomials with `polynomial_length` terms.
+fn run_metadata(args: &Config, polynomial_length: usize) -> RunMetadata {
+    RunMetadata {
+        matrix_size: args.matrix_size,
+        polynomial_length,
+        mutated_polynomials_to_evaluate: args.mutated_polynomials_to_evaluate,
+        matrices_to_fuzz: args.matrices_to_fuzz,
+        imported_from: None,
+    }
+}
+
+// Writes the results of a mutate, map-space or resume run, sorted by polynomial.
+fn print_polynomials(
+    args: Config,
+    current_state: CurrentState,
+    run: RunMetadata,
+    started_at: SystemTime,
+    output_path: &Path,
+) -> Result<()> {
+    let output_format = args.output_format;
+    let output = Output::from_state(current_state, args, run, started_at);
     info!(
         "Total number of interesting polynomials found {}",
-        polynomials.len()
+        output.interesting_polynomials.len()
     );
-    for poly in &polynomials {
-        println!("{}", poly.to_string());
+    for poly in &output.interesting_polynomials {
+        println!("{}", poly);
     }
-
-    let json_object = serde_json::to_string(&Output {
-        interesting_polynomials: polynomials,
-    })
-    .expect("Object will be converted to JSON string");
-    File::create("output.json").expect("file should open read only");
-    fs::write("output.json", json_object).expect("file should open read only");
+    export::write_output(output_path, &output, output_format)
 }
 
-fn mode_test_polynomial(args: Config) {
+fn mode_test_polynomial(args: Config, files: &Files) -> Result<()> {
     let start = Instant::now();
-    let polynomial = if args.starting_polynomial.len() == 0 {
-        polynomial::Polynomial::from_vec(vec![1.0, 1.0, 1.0, 1.0, 1.0], args.matrix_size)
-    } else {
-        polynomial::Polynomial::from_vec(args.starting_polynomial, args.matrix_size)
-    };
-    let polynomial_verifier = polynomial_verifier::PolynomialVerifier::new(
-        args.matrices_to_fuzz,
+    let seed = args.seed();
+    let polynomial = starting_polynomial(&args);
+    let (polynomial_verifier, _) = generate_polynomial_verifier(
+        &args.verifier_config(seed)?,
         args.matrix_size,
         polynomial.len(),
     );
-    let verify_polynomial = polynomial_verifier.test_polynomial(&polynomial);
+    let verdict = polynomial_verifier.verify_parallel(&polynomial);
     let duration = start.elapsed();
     info!("Total time elapsed verifying polynomial {:?}", duration);
-    if verify_polynomial {
+    if verdict.preserves {
         println!(
-            "The polynomial {} probably preserves {}-by-{} matrices.",
-            polynomial.to_string(),
-            args.matrix_size,
-            args.matrix_size
+            "The polynomial {} probably preserves {}-by-{} matrices ({}).",
+            polynomial, args.matrix_size, args.matrix_size, verdict.check
         );
     } else {
         println!(
-            "The polynomial {} does not preserves {}-by-{} matrices.",
-            polynomial.to_string(),
-            args.matrix_size,
-            args.matrix_size
+            "The polynomial {} does not preserves {}-by-{} matrices ({}).",
+            polynomial, args.matrix_size, args.matrix_size, verdict.check
         );
+        if let Some(counterexample) = &verdict.counterexample {
+            println!("{}", counterexample);
+        }
     }
+
+    write_json(
+        &files.output,
+        &TestOutput {
+            polynomial,
+            matrix_size: args.matrix_size,
+            seed,
+            tolerance: args.tolerance,
+            verdict,
+        },
+    )
 }
 
-fn mode_mutate_polynomial(args: Config) {
+fn mode_mutate_polynomial(args: Config, files: &Files) -> Result<()> {
     let start = Instant::now();
-    let polynomial = if args.starting_polynomial.len() == 0 {
-        Polynomial::from_vec(vec![1.0, 1.0, 1.0, 1.0, 1.0], args.matrix_size)
-    } else {
-        Polynomial::from_vec(args.starting_polynomial, args.matrix_size)
-    };
-    let mut interesting_polynomials = mutate_polynomial_from_beginning(
-        polynomial,
-        args.matrices_to_fuzz,
-        args.mutated_polynomials_to_evaluate,
-        args.number_of_generations,
-    );
-    interesting_polynomials.sort();
+    let started_at = SystemTime::now();
+    let seed = args.seed();
+    let polynomial = starting_polynomial(&args);
+    let run = run_metadata(&args, polynomial.len());
+    let current_state = mutate_polynomial_from_beginning(polynomial, &args, seed, &files.state)?;
     let duration = start.elapsed();
     info!("Total time elapsed generating polynomials {:?}", duration);
-    print_polynomials(interesting_polynomials);
+    print_polynomials(args, current_state, run, started_at, &files.output)
 }
 
-fn mode_map_space(args: Config) {
+fn mode_map_space(args: Config, files: &Files) -> Result<()> {
     let start = Instant::now();
+    let started_at = SystemTime::now();
     let polynomial = Polynomial::from_element(args.polynomial_length, args.matrix_size, 1.0);
-    let mut interesting_polynomials = mutate_polynomial_from_beginning(
-        polynomial,
-        args.matrices_to_fuzz,
-        args.mutated_polynomials_to_evaluate,
-        args.number_of_generations,
-    );
-    interesting_polynomials.sort();
+    let seed = args.seed();
+    let run = run_metadata(&args, args.polynomial_length);
+    let current_state = mutate_polynomial_from_beginning(polynomial, &args, seed, &files.state)?;
     let duration = start.elapsed();
     info!("Total time elapsed generating polynomials {:?}", duration);
-    print_polynomials(interesting_polynomials);
+    print_polynomials(args, current_state, run, started_at, &files.output)
 }
 
-fn mode_return_state(args: Config) {
+// Resumes the run in the state file with the config and seed it was started with, running
+// `generations` in total when given. Settings in the startup file that differ from the run are
+// reported and ignored, or refused with `strict`. States saved before the config was recorded
+// resume with the startup file.
+fn mode_return_state(
+    mut args: Config,
+    strict: bool,
+    generations: Option<usize>,
+    files: &Files,
+) -> Result<()> {
     let start = Instant::now();
-    let current_state = CurrentState::load_state();
-    let mut interesting_polynomials = mutate_polynomial(
+    let started_at = SystemTime::now();
+    let mut current_state = CurrentState::load_state(&files.state)?;
+    let seed = current_state.seed;
+    info!("Resuming with seed {}", seed);
+    let (mut args, run) = match current_state.config.take() {
+        Some(stored) => {
+            let differences = args.resume_differences(&stored);
+            if strict && !differences.is_empty() {
+                return Err(Error::InvalidConfig(format!(
+                    "the config differs from the run in {}: {}",
+                    files.state.display(),
+                    differences.join(", ")
+                )));
+            }
+            for difference in &differences {
+                warn!("Ignoring {} when resuming", difference);
+            }
+            let resumed = stored.resumed_with(&args);
+            let polynomial_length = current_state
+                .starting_mutated_polynomials
+                .first()
+                .map_or(resumed.polynomial_length, Polynomial::len);
+            let run = run_metadata(&resumed, polynomial_length);
+            (resumed, run)
+        }
+        None => {
+            warn!(
+                "{} does not record the config of the run, resuming with the startup file",
+                files.state.display()
+            );
+            let mut run = run_metadata(&args, 0);
+            if let Some(first) = current_state.starting_mutated_polynomials.first() {
+                run.matrix_size = first.get_size();
+                run.polynomial_length = first.len();
+            }
+            run.mutated_polynomials_to_evaluate = current_state.starting_mutated_polynomials.len();
+            args.seed = Some(seed);
+            (args, run)
+        }
+    };
+    if let Some(generations) = generations {
+        args.number_of_generations = generations;
+    }
+    args.validate(Mode::Resume)?;
+    current_state.config = Some(args.clone());
+    current_state.set_checkpoint_config(&args.checkpoint);
+    let current_state = mutate_polynomial(
         current_state,
-        args.matrices_to_fuzz,
+        &args.verifier_config(seed)?,
         args.number_of_generations,
-    );
-    interesting_polynomials.sort();
+        &args.progress,
+    )?;
     let duration = start.elapsed();
     info!("Total time elapsed generating polynomials {:?}", duration);
-    print_polynomials(interesting_polynomials);
+    print_polynomials(args, current_state, run, started_at, &files.output)
+}
+
+fn mode_cutting_plane(args: Config, files: &Files) -> Result<()> {
+    let start = Instant::now();
+    let seed = args.seed();
+    let polynomial = starting_polynomial(&args);
+    let (polynomial_verifier, _) = generate_polynomial_verifier(
+        &args.verifier_config(seed)?,
+        args.matrix_size,
+        polynomial.len(),
+    );
+    let result = cutting_plane::minimize(&polynomial, &args.cutting_plane, &polynomial_verifier);
+    info!(
+        "Total time elapsed in the cutting plane loop {:?}",
+        start.elapsed()
+    );
+    match &result.polynomial {
+        Some(minimized) => println!(
+            "{} after {} iterations ({:?}, {} cuts from the sample, {} from the local search)",
+            minimized, result.iterations, result.status, result.sample_cuts, result.search_cuts
+        ),
+        None => println!(
+            "No minimized polynomial for {} ({:?})",
+            polynomial, result.status
+        ),
+    }
+
+    write_json(
+        &files.output,
+        &CuttingPlaneOutput {
+            starting_polynomial: polynomial,
+            matrix_size: args.matrix_size,
+            seed,
+            tolerance: args.tolerance,
+            cutting_plane: args.cutting_plane,
+            result,
+        },
+    )
+}
+
+fn mode_import(inputs: &[PathBuf], output: &Path, output_format: OutputFormat) -> Result<()> {
+    if inputs.len() > 1 {
+        fs::create_dir_all(output).map_err(|source| Error::io(output, source))?;
+    }
+    for input in inputs {
+        let imported = legacy::import(input)?;
+        let output_path = if inputs.len() > 1 {
+            let mut name = input.file_name().unwrap_or_default().to_os_string();
+            name.push(".");
+            name.push(output_format.extension());
+            output.join(name)
+        } else {
+            output.to_path_buf()
+        };
+        println!(
+            "Imported {} polynomials from {} to {}",
+            imported.interesting_polynomials.len(),
+            input.display(),
+            output_path.display()
+        );
+        export::write_output(&output_path, &imported, output_format)?;
+    }
+    Ok(())
+}
+
+fn mode_recheck(args: Config, input: &Path, files: &Files) -> Result<()> {
+    let start = Instant::now();
+    let seed = args.seed();
+    let output = Output::load(input)?;
+    let mut polynomials = output.interesting_polynomials;
+    for polynomial in polynomials.iter_mut() {
+        polynomial.set_size(args.matrix_size);
+    }
+    let polynomial_length = polynomials.iter().map(Polynomial::len).max().unwrap_or(0);
+    let (polynomial_verifier, _) = generate_polynomial_verifier(
+        &args.verifier_config(seed)?,
+        args.matrix_size,
+        polynomial_length,
+    );
+
+    let mut checked_polynomials = Vec::new();
+    for polynomial in polynomials {
+        let verdict = polynomial_verifier.verify_parallel(&polynomial);
+        println!(
+            "{} {} ({})",
+            if verdict.preserves { "pass" } else { "fail" },
+            polynomial,
+            verdict.check
+        );
+        checked_polynomials.push(CheckedPolynomial {
+            polynomial,
+            verdict,
+        });
+    }
+    let failed = checked_polynomials
+        .iter()
+        .filter(|checked| !checked.verdict.preserves)
+        .count();
+    info!(
+        "{} of {} polynomials failed in {:?}",
+        failed,
+        checked_polynomials.len(),
+        start.elapsed()
+    );
+
+    write_json(
+        &files.output,
+        &RecheckOutput {
+            matrix_size: args.matrix_size,
+            seed,
+            tolerance: args.tolerance,
+            checked_polynomials,
+        },
+    )
+}
+
+fn mode_list_runs(runs: &Path) -> Result<()> {
+    let run_directories = RunDirectory::list(runs)?;
+    if run_directories.is_empty() {
+        println!("No runs in {}", runs.display());
+    }
+    for run_directory in run_directories {
+        match run_directory.status() {
+            Ok(status) => println!("{}", status),
+            Err(error) => println!("{}: {}", run_directory.name(), error),
+        }
+    }
+    Ok(())
+}
+
+fn mode_run_status(runs: &Path, name: &str) -> Result<()> {
+    let run_directory = RunDirectory::open(runs, name)?;
+    let status = run_directory.status()?;
+    println!("{}", status);
+    if let Some(progress) = &status.progress {
+        println!("seed: {}", progress.seed);
+    }
+    println!("config: {}", run_directory.config_path().display());
+    println!("state: {}", run_directory.state_path().display());
+    for format in [OutputFormat::Json, OutputFormat::Csv, OutputFormat::Binary] {
+        let output_path = run_directory.output_path(format);
+        if output_path.exists() {
+            println!("output: {}", output_path.display());
+        }
+    }
+    println!("log: {}", run_directory.log_path().display());
+    Ok(())
+}
+
+// Writes log lines to standard error below the progress line, see `progress::suspend`.
+struct Stderr;
+
+impl Write for Stderr {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        progress::suspend(|| io::stderr().write_all(buffer))?;
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+// Writes log lines to standard error and to the log file of a run.
+struct Tee(fs::File);
+
+impl Write for Tee {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        Stderr.write_all(buffer)?;
+        self.0.write_all(buffer)?;
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.0.flush()
+    }
+}
+
+// Logs to standard error without breaking the progress line, and for a named run also to its
+// log file at info level unless `RUST_LOG` says otherwise.
+fn init_logger(run_directory: Option<&RunDirectory>) -> Result<()> {
+    match run_directory {
+        Some(run_directory) => {
+            let log = run_directory.open_log()?;
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+                .target(env_logger::Target::Pipe(Box::new(Tee(log))))
+                .init();
+        }
+        None => env_logger::Builder::from_env(env_logger::Env::default())
+            .target(env_logger::Target::Pipe(Box::new(Stderr)))
+            .init(),
+    }
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Import { inputs }) => {
+            env_logger::init();
+            let output_format = cli.overrides.output_format.unwrap_or_default();
+            let output = cli.output.unwrap_or_else(|| match inputs.len() {
+                1 => default_output(output_format),
+                _ => PathBuf::from("output"),
+            });
+            return mode_import(inputs, &output, output_format);
+        }
+        Some(Command::Runs) => return mode_list_runs(&cli.runs),
+        Some(Command::Status { name }) => return mode_run_status(&cli.runs, name),
+        _ => {}
+    }
+    let run_name = match &cli.command {
+        Some(Command::Resume {
+            name: Some(name), ..
+        }) => Some(name),
+        _ => cli.run.as_ref(),
+    };
+    let run_directory = run_name
+        .map(|name| RunDirectory::new(&cli.runs, name))
+        .transpose()?;
+
+    // A run that has been started reads the config it was started with.
+    let config_path = match &run_directory {
+        Some(run_directory) if run_directory.config_path().exists() => run_directory.config_path(),
+        _ => cli.config,
+    };
+    let mut args = Config::load(&config_path)?;
+    cli.overrides.apply(&mut args);
+    let command = match (cli.command, args.mode) {
+        (Some(command), _) => command,
+        (None, Some(mode)) => mode_to_command(mode),
+        (None, None) => {
+            return Err(Error::InvalidConfig(String::from(
+                "mode must be set to \"test\", \"mutate\", \"map-space\", \"resume\" or \
+                 \"cutting-plane\", \
+                 or pass a command",
+            )))
+        }
+    };
+    let files = match &run_directory {
+        Some(run_directory) => Files {
+            output: run_directory.output_path(args.output_format),
+            state: run_directory.state_path(),
+        },
+        None => Files {
+            output: cli
+                .output
+                .unwrap_or_else(|| default_output(command_format(&command, args.output_format))),
+            state: cli.state,
+        },
+    };
+    if let Command::Test { coefficients }
+    | Command::Mutate { coefficients }
+    | Command::CuttingPlane { coefficients } = &command
+    {
+        if !coefficients.is_empty() {
+            args.starting_polynomial = coefficients.clone();
+        }
+    }
+    // Resume checks the config of the run it resumes instead.
+    if !matches!(command, Command::Resume { .. }) {
+        args.validate(command_mode(&command))?;
+    }
+    // Held until the command returns, so no other process works on the same run.
+    let _lock = match &run_directory {
+        Some(run_directory) => Some(match command {
+            Command::Mutate { .. } | Command::MapSpace => {
+                // Running the run again without a command resumes it.
+                let mut run_config = args.clone();
+                run_config.mode = Some(Mode::Resume);
+                run_directory.create(&run_config)?
+            }
+            Command::Resume { .. } => {
+                RunDirectory::open(&cli.runs, run_directory.name())?.lock()?
+            }
+            _ => {
+                return Err(Error::InvalidConfig(String::from(
+                    "named runs are only supported by mutate, map-space and resume",
+                )))
+            }
+        }),
+        None => None,
+    };
+    init_logger(run_directory.as_ref())?;
+    if let Some(run_directory) = &run_directory {
+        info!(
+            "Running {} as run \"{}\"",
+            command_mode(&command),
+            run_directory.name()
+        );
+    }
+    // The runs that checkpoint stop cleanly on Ctrl-C, see `shutdown`.
+    if matches!(
+        command,
+        Command::Mutate { .. } | Command::MapSpace | Command::Resume { .. }
+    ) {
+        shutdown::install();
+    }
+    match command {
+        Command::Test { .. } => mode_test_polynomial(args, &files),
+        Command::Mutate { .. } => mode_mutate_polynomial(args, &files),
+        Command::MapSpace => mode_map_space(args, &files),
+        Command::Resume {
+            strict,
+            generations,
+            ..
+        } => mode_return_state(args, strict, generations, &files),
+        Command::CuttingPlane { .. } => mode_cutting_plane(args, &files),
+        Command::Recheck { input } => mode_recheck(args, &input, &files),
+        Command::Import { .. } | Command::Runs | Command::Status { .. } => {
+            unreachable!("returned before loading the config")
+        }
+    }
 }
 
 fn main() {
-    let args = read_user_from_file();
-    env_logger::init();
-    match i32_to_polynomial_mode(args.mode) {
-        PolynomialMode::TestPolynomial => mode_test_polynomial(args),
-        PolynomialMode::MutatePolynomial => mode_mutate_polynomial(args),
-        PolynomialMode::MapSpace => mode_map_space(args),
-        PolynomialMode::ReturnState => mode_return_state(args),
-        PolynomialMode::Error => error!("mode must be set to 1,2, or 3"),
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+    // The state and partial results are saved by now.
+    if shutdown::requested() {
+        eprintln!("Interrupted, the run was saved and can be resumed");
+        process::exit(130);
     }
 }
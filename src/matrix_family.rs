@@ -0,0 +1,423 @@
+use nalgebra::DMatrix;
+use rand::distributions::Uniform;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+// Classes of nonnegative matrices the verifier can sample from. Each family is selected by its
+// name in the `matrix_families` config option.
+pub trait MatrixFamily: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // One line saying which matrices the family draws, recorded in the results.
+    fn description(&self) -> &'static str;
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64>;
+}
+
+pub const FAMILY_NAMES: [&str; 10] = [
+    "circulant",
+    "toeplitz",
+    "symmetric",
+    "stochastic",
+    "doubly_stochastic",
+    "tridiagonal",
+    "companion",
+    "upper_triangular",
+    "permutation_weighted",
+    "random",
+];
+
+pub fn from_name(name: &str) -> Option<Box<dyn MatrixFamily>> {
+    match name {
+        "circulant" => Some(Box::new(Circulant)),
+        "toeplitz" => Some(Box::new(Toeplitz)),
+        "symmetric" => Some(Box::new(Symmetric)),
+        "stochastic" => Some(Box::new(Stochastic)),
+        "doubly_stochastic" => Some(Box::new(DoublyStochastic)),
+        "tridiagonal" => Some(Box::new(Tridiagonal)),
+        "companion" => Some(Box::new(Companion)),
+        "upper_triangular" => Some(Box::new(UpperTriangular)),
+        "permutation_weighted" => Some(Box::new(PermutationWeighted)),
+        "random" => Some(Box::new(Random)),
+        _ => None,
+    }
+}
+
+// Looks up every name, returning the first unknown name on failure.
+pub fn from_names(names: &[String]) -> Result<Vec<Box<dyn MatrixFamily>>, String> {
+    names
+        .iter()
+        .map(|name| from_name(name).ok_or_else(|| name.clone()))
+        .collect()
+}
+
+pub(crate) fn entry_distribution() -> Uniform<f64> {
+    Uniform::new(1.0, 100.0)
+}
+
+// The permutation matrix sending e_i to e_{i+1}.
+pub fn fundamental_circulant(matrix_size: usize) -> DMatrix<f64> {
+    let mut fundamental_circulant = DMatrix::<f64>::identity(matrix_size, matrix_size);
+    for i in 1..matrix_size {
+        fundamental_circulant.swap_rows(0, i);
+    }
+    fundamental_circulant
+}
+
+fn random_permutation(matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+    let mut columns: Vec<usize> = (0..matrix_size).collect();
+    columns.shuffle(rng);
+    let mut permutation = DMatrix::<f64>::zeros(matrix_size, matrix_size);
+    for (row, column) in columns.into_iter().enumerate() {
+        permutation[(row, column)] = 1.0;
+    }
+    permutation
+}
+
+// c_0 I + c_1 P + ... + c_{n-1} P^{n-1} for the fundamental circulant P.
+pub struct Circulant;
+
+impl MatrixFamily for Circulant {
+    fn name(&self) -> &'static str {
+        "circulant"
+    }
+
+    fn description(&self) -> &'static str {
+        "c_0 I + c_1 P + ... + c_{n-1} P^{n-1} for the fundamental circulant P"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let fundamental_circulant = fundamental_circulant(matrix_size);
+        let distribution = entry_distribution();
+        let mut random_circulant = DMatrix::<f64>::zeros(matrix_size, matrix_size);
+        let mut working_power = DMatrix::<f64>::identity(matrix_size, matrix_size);
+        for _ in 0..matrix_size {
+            random_circulant += working_power.scale(rng.sample(distribution));
+            working_power *= &fundamental_circulant;
+        }
+        random_circulant
+    }
+}
+
+// Constant along each diagonal.
+pub struct Toeplitz;
+
+impl MatrixFamily for Toeplitz {
+    fn name(&self) -> &'static str {
+        "toeplitz"
+    }
+
+    fn description(&self) -> &'static str {
+        "constant along each diagonal"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        let diagonals: Vec<f64> = (0..(2 * matrix_size).saturating_sub(1))
+            .map(|_| rng.sample(distribution))
+            .collect();
+        DMatrix::<f64>::from_fn(matrix_size, matrix_size, |row, column| {
+            diagonals[row + matrix_size - 1 - column]
+        })
+    }
+}
+
+pub struct Symmetric;
+
+impl MatrixFamily for Symmetric {
+    fn name(&self) -> &'static str {
+        "symmetric"
+    }
+
+    fn description(&self) -> &'static str {
+        "symmetric matrices"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let matrix = Random.generate(matrix_size, rng);
+        (&matrix + matrix.transpose()).scale(0.5)
+    }
+}
+
+// Every row sums to one.
+pub struct Stochastic;
+
+impl MatrixFamily for Stochastic {
+    fn name(&self) -> &'static str {
+        "stochastic"
+    }
+
+    fn description(&self) -> &'static str {
+        "each row sums to one"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let mut matrix = Random.generate(matrix_size, rng);
+        for mut row in matrix.row_iter_mut() {
+            let sum = row.sum();
+            row /= sum;
+        }
+        matrix
+    }
+}
+
+// A random convex combination of permutation matrices, which by Birkhoff's theorem covers every
+// doubly stochastic matrix.
+pub struct DoublyStochastic;
+
+impl MatrixFamily for DoublyStochastic {
+    fn name(&self) -> &'static str {
+        "doubly_stochastic"
+    }
+
+    fn description(&self) -> &'static str {
+        "convex combinations of permutation matrices"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        let weights: Vec<f64> = (0..matrix_size).map(|_| rng.sample(distribution)).collect();
+        let total: f64 = weights.iter().sum();
+        let mut matrix = DMatrix::<f64>::zeros(matrix_size, matrix_size);
+        for weight in weights {
+            matrix += random_permutation(matrix_size, rng).scale(weight / total);
+        }
+        matrix
+    }
+}
+
+pub struct Tridiagonal;
+
+impl MatrixFamily for Tridiagonal {
+    fn name(&self) -> &'static str {
+        "tridiagonal"
+    }
+
+    fn description(&self) -> &'static str {
+        "nonzero only on the main diagonal and the diagonals next to it"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        DMatrix::<f64>::from_fn(matrix_size, matrix_size, |row, column| {
+            if row.max(column) - row.min(column) <= 1 {
+                rng.sample(distribution)
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+// Companion matrix of x^n - c_{n-1} x^{n-1} - ... - c_0 with every c_i positive.
+pub struct Companion;
+
+impl MatrixFamily for Companion {
+    fn name(&self) -> &'static str {
+        "companion"
+    }
+
+    fn description(&self) -> &'static str {
+        "companion matrices with a nonnegative last column"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        DMatrix::<f64>::from_fn(matrix_size, matrix_size, |row, column| {
+            if column + 1 == matrix_size {
+                rng.sample(distribution)
+            } else if row == column + 1 {
+                1.0
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+pub struct UpperTriangular;
+
+impl MatrixFamily for UpperTriangular {
+    fn name(&self) -> &'static str {
+        "upper_triangular"
+    }
+
+    fn description(&self) -> &'static str {
+        "zero below the main diagonal"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        DMatrix::<f64>::from_fn(matrix_size, matrix_size, |row, column| {
+            if row <= column {
+                rng.sample(distribution)
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+// A permutation matrix with positive weights in place of its ones.
+pub struct PermutationWeighted;
+
+impl MatrixFamily for PermutationWeighted {
+    fn name(&self) -> &'static str {
+        "permutation_weighted"
+    }
+
+    fn description(&self) -> &'static str {
+        "a permutation matrix with positive weights in place of its ones"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        let mut matrix = random_permutation(matrix_size, rng);
+        for entry in matrix.iter_mut() {
+            if *entry > 0.0 {
+                *entry = rng.sample(distribution);
+            }
+        }
+        matrix
+    }
+}
+
+// Every entry drawn independently.
+pub struct Random;
+
+impl MatrixFamily for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn description(&self) -> &'static str {
+        "every entry drawn independently"
+    }
+
+    fn generate(&self, matrix_size: usize, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = entry_distribution();
+        DMatrix::<f64>::from_fn(matrix_size, matrix_size, |_, _| rng.sample(distribution))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SIZES: [usize; 4] = [1, 2, 3, 5];
+    const SAMPLES: usize = 20;
+
+    // Generates SAMPLES matrices of each size in SIZES.
+    fn samples(family: &dyn MatrixFamily) -> Vec<DMatrix<f64>> {
+        let mut rng = StdRng::seed_from_u64(1);
+        SIZES
+            .iter()
+            .flat_map(|size| std::iter::repeat_n(*size, SAMPLES))
+            .map(|size| {
+                let matrix = family.generate(size, &mut rng);
+                assert_eq!(matrix.shape(), (size, size), "{}", family.name());
+                matrix
+            })
+            .collect()
+    }
+
+    // Checks that the entries are zero exactly where `is_zero` says so.
+    fn assert_zero_structure(family: &dyn MatrixFamily, is_zero: impl Fn(usize, usize) -> bool) {
+        for matrix in samples(family) {
+            for row in 0..matrix.nrows() {
+                for column in 0..matrix.ncols() {
+                    let entry = matrix[(row, column)];
+                    if is_zero(row, column) {
+                        assert_eq!(entry, 0.0, "{} at {:?}", family.name(), (row, column));
+                    } else {
+                        assert!(entry > 0.0, "{} at {:?}", family.name(), (row, column));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_family_is_nonnegative() {
+        assert_eq!(from_names(&[]).unwrap().len(), 0);
+        let names: Vec<String> = FAMILY_NAMES.iter().map(|name| name.to_string()).collect();
+        let families = from_names(&names).unwrap();
+        assert_eq!(families.len(), FAMILY_NAMES.len());
+        for (family, name) in families.iter().zip(FAMILY_NAMES) {
+            assert_eq!(family.name(), name);
+            for matrix in samples(family.as_ref()) {
+                assert!(matrix.iter().all(|entry| *entry >= 0.0), "{}", name);
+            }
+        }
+        assert_eq!(
+            from_names(&["circulant".to_string(), "hankel".to_string()]).err(),
+            Some("hankel".to_string())
+        );
+    }
+
+    #[test]
+    fn circulant_matrices_are_circulant() {
+        for matrix in samples(&Circulant) {
+            let size = matrix.nrows();
+            for row in 0..size {
+                for column in 0..size {
+                    // Each row is the row above shifted one place to the right.
+                    let above = ((row + size - 1) % size, (column + size - 1) % size);
+                    assert_eq!(matrix[(row, column)], matrix[above]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stochastic_matrices_sum_to_one() {
+        for matrix in samples(&Stochastic) {
+            for row in matrix.row_iter() {
+                assert!((row.sum() - 1.0).abs() < 1e-12);
+            }
+        }
+        for matrix in samples(&DoublyStochastic) {
+            for row in matrix.row_iter() {
+                assert!((row.sum() - 1.0).abs() < 1e-12);
+            }
+            for column in matrix.column_iter() {
+                assert!((column.sum() - 1.0).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn families_have_their_zero_structure() {
+        assert_zero_structure(&Tridiagonal, |row, column| {
+            row.max(column) - row.min(column) > 1
+        });
+        assert_zero_structure(&UpperTriangular, |row, column| row > column);
+        for matrix in samples(&Companion) {
+            let size = matrix.nrows();
+            for row in 0..size {
+                for column in 0..size {
+                    let entry = matrix[(row, column)];
+                    if column + 1 == size {
+                        assert!(entry > 0.0);
+                    } else if row == column + 1 {
+                        assert_eq!(entry, 1.0);
+                    } else {
+                        assert_eq!(entry, 0.0);
+                    }
+                }
+            }
+        }
+        // One positive entry in every row and every column.
+        for matrix in samples(&PermutationWeighted) {
+            let positive = |entries: &[f64]| entries.iter().filter(|entry| **entry > 0.0).count();
+            for row in matrix.row_iter() {
+                assert_eq!(positive(&row.iter().copied().collect::<Vec<_>>()), 1);
+            }
+            for column in matrix.column_iter() {
+                assert_eq!(positive(column.as_slice()), 1);
+            }
+        }
+    }
+}
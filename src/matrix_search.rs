@@ -0,0 +1,152 @@
+// Local search for nonnegative matrices A that make p(A) as negative as possible. The entries are
+// written as squares of free parameters, A_ij = x_ij^2, so the Nelder-Mead method can move in any
+// direction while A stays nonnegative. Every restart begins at a random matrix of random scale,
+// since the worst matrices for a polynomial can be small or large.
+use crate::polynomial::Polynomial;
+use nalgebra::{DMatrix, DVector};
+use rand::Rng;
+
+// Score evaluations allowed per restart, for every entry of the matrix.
+const EVALUATIONS_PER_ENTRY: usize = 200;
+
+// A restart stops once the scores across its simplex agree to within this.
+const CONVERGENCE: f64 = 1e-12;
+
+// The best matrix found by `search`.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub matrix: DMatrix<f64>,
+    pub score: f64,
+}
+
+// The smallest entry of p(A) divided by the largest in absolute value, so it lies in [-1, 1] and
+// is negative exactly when p(A) has a negative entry. Dividing keeps the search from running off
+// to ever larger matrices once it finds a negative entry.
+pub fn score(polynomial: &Polynomial, matrix: &DMatrix<f64>) -> f64 {
+    let result = polynomial.evaluate_matrix(matrix);
+    let amax = result.amax();
+    let score = if amax == 0.0 {
+        0.0
+    } else {
+        result.min() / amax
+    };
+    if score.is_finite() {
+        score
+    } else {
+        f64::INFINITY
+    }
+}
+
+// Runs `restarts` local searches and returns the matrix with the lowest score, or None when
+// `restarts` is zero.
+pub fn search<R: Rng>(
+    polynomial: &Polynomial,
+    restarts: usize,
+    rng: &mut R,
+) -> Option<SearchResult> {
+    let size = polynomial.get_size();
+    let dimension = size * size;
+    let mut best: Option<SearchResult> = None;
+    for _ in 0..restarts {
+        let scale: f64 = 10f64.powf(rng.gen_range(-1.0..1.0));
+        let start = DVector::from_fn(dimension, |_, _| (scale * rng.gen::<f64>()).sqrt());
+        let (x, score) = nelder_mead(
+            |x| score(polynomial, &to_matrix(size, x)),
+            start,
+            0.5 * scale.sqrt(),
+            EVALUATIONS_PER_ENTRY * dimension,
+        );
+        if best.as_ref().is_none_or(|best| score < best.score) {
+            best = Some(SearchResult {
+                matrix: to_matrix(size, &x),
+                score,
+            });
+        }
+    }
+    best
+}
+
+fn to_matrix(size: usize, x: &DVector<f64>) -> DMatrix<f64> {
+    DMatrix::from_iterator(size, size, x.iter().map(|value| value * value))
+}
+
+// Minimizes `f` from `start` with the Nelder-Mead method, using the standard reflection,
+// expansion, contraction and shrink coefficients. `step` is the size of the first simplex.
+fn nelder_mead<F>(
+    f: F,
+    start: DVector<f64>,
+    step: f64,
+    max_evaluations: usize,
+) -> (DVector<f64>, f64)
+where
+    F: Fn(&DVector<f64>) -> f64,
+{
+    let dimension = start.len();
+    let mut simplex = vec![start.clone()];
+    for i in 0..dimension {
+        let mut vertex = start.clone();
+        vertex[i] += step;
+        simplex.push(vertex);
+    }
+    let mut values: Vec<f64> = simplex.iter().map(&f).collect();
+    let mut evaluations = simplex.len();
+
+    while evaluations < max_evaluations {
+        let mut order: Vec<usize> = (0..simplex.len()).collect();
+        order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+        simplex = order.iter().map(|i| simplex[*i].clone()).collect();
+        values = order.iter().map(|i| values[*i]).collect();
+
+        let worst = dimension;
+        if (values[worst] - values[0]).abs() < CONVERGENCE {
+            break;
+        }
+
+        let centroid = simplex[..worst]
+            .iter()
+            .fold(DVector::zeros(dimension), |sum, vertex| sum + vertex)
+            / dimension as f64;
+        let reflected = &centroid + (&centroid - &simplex[worst]);
+        let reflected_value = f(&reflected);
+        evaluations += 1;
+
+        if reflected_value < values[0] {
+            let expanded = &centroid + (&reflected - &centroid) * 2.0;
+            let expanded_value = f(&expanded);
+            evaluations += 1;
+            if expanded_value < reflected_value {
+                simplex[worst] = expanded;
+                values[worst] = expanded_value;
+            } else {
+                simplex[worst] = reflected;
+                values[worst] = reflected_value;
+            }
+        } else if reflected_value < values[worst - 1] {
+            simplex[worst] = reflected;
+            values[worst] = reflected_value;
+        } else {
+            let contracted = if reflected_value < values[worst] {
+                &centroid + (&reflected - &centroid) * 0.5
+            } else {
+                &centroid + (&simplex[worst] - &centroid) * 0.5
+            };
+            let contracted_value = f(&contracted);
+            evaluations += 1;
+            if contracted_value < values[worst].min(reflected_value) {
+                simplex[worst] = contracted;
+                values[worst] = contracted_value;
+            } else {
+                for i in 1..simplex.len() {
+                    simplex[i] = &simplex[0] + (&simplex[i] - &simplex[0]) * 0.5;
+                    values[i] = f(&simplex[i]);
+                }
+                evaluations += dimension;
+            }
+        }
+    }
+
+    let best = (0..values.len())
+        .min_by(|a, b| values[*a].total_cmp(&values[*b]))
+        .unwrap_or(0);
+    (simplex[best].clone(), values[best])
+}
@@ -0,0 +1,299 @@
+use crate::config::Config;
+use crate::current_state::CurrentState;
+use crate::error::{Error, Result};
+use crate::export;
+use crate::polynomial::Polynomial;
+use crate::shutdown;
+use crate::tolerance::Tolerance;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Version of the results file format. Version 1 files only held the polynomials, seed and
+// tolerance and have no `version` field. Every field added since is optional when loading.
+pub const OUTPUT_VERSION: u32 = 2;
+
+// The results file written by `mutate`, `map-space`, `resume` and `import` in the JSON format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Output {
+    #[serde(default = "first_version")]
+    pub version: u32,
+    // Version of the program that found the polynomials, None for imported and version 1 files.
+    #[serde(default)]
+    pub crate_version: Option<String>,
+    pub interesting_polynomials: Vec<Polynomial>,
+    // Where each polynomial came from, in the same order. Empty when unknown.
+    #[serde(default)]
+    pub provenance: Vec<Option<Provenance>>,
+    // None for imported runs, which did not record their seed.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub tolerance: Tolerance,
+    #[serde(default)]
+    pub run: Option<RunMetadata>,
+    // The config after command line overrides.
+    #[serde(default)]
+    pub config: Option<Config>,
+    #[serde(default)]
+    pub matrix_sample: Vec<SampleDescription>,
+    // Seconds since the Unix epoch.
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub elapsed_seconds: Option<f64>,
+    #[serde(default)]
+    pub generations: Vec<GenerationReport>,
+    // The run was stopped by a signal and holds the polynomials found up to then, its state can be
+    // resumed.
+    #[serde(default)]
+    pub interrupted: bool,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl Output {
+    // Results of the current version with every optional field empty.
+    pub fn new(interesting_polynomials: Vec<Polynomial>, tolerance: Tolerance) -> Self {
+        Output {
+            version: OUTPUT_VERSION,
+            crate_version: None,
+            interesting_polynomials,
+            provenance: Vec::new(),
+            seed: None,
+            tolerance,
+            run: None,
+            config: None,
+            matrix_sample: Vec::new(),
+            started_at: None,
+            finished_at: None,
+            elapsed_seconds: None,
+            generations: Vec::new(),
+            interrupted: false,
+        }
+    }
+
+    // The results of a mutate, map-space or resume run from its final state, sorted by
+    // polynomial. Marked as interrupted when a signal stopped the run.
+    pub fn from_state(
+        current_state: CurrentState,
+        config: Config,
+        run: RunMetadata,
+        started_at: SystemTime,
+    ) -> Self {
+        let mut found: Vec<(Polynomial, Option<Provenance>)> = current_state
+            .interesting_polynomials
+            .into_iter()
+            .zip(current_state.provenance)
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        let (polynomials, provenance): (Vec<Polynomial>, Vec<Option<Provenance>>) =
+            found.into_iter().unzip();
+
+        let mut output = Output::new(polynomials, config.tolerance);
+        output.crate_version = Some(String::from(env!("CARGO_PKG_VERSION")));
+        output.provenance = provenance;
+        output.seed = Some(current_state.seed);
+        output.run = Some(run);
+        output.matrix_sample = config.matrix_sample();
+        output.started_at = Some(unix_time(started_at));
+        output.finished_at = Some(unix_time(SystemTime::now()));
+        output.elapsed_seconds = Some(started_at.elapsed().unwrap_or_default().as_secs_f64());
+        output.generations = current_state.generations;
+        output.interrupted = shutdown::requested();
+        output.config = Some(config);
+        output
+    }
+
+    // Loads a JSON results file of this or any earlier version, or the polynomials of a CSV or
+    // binary results file. The format is told by the start of the file, not its extension. CSV
+    // and binary files also record the generation of each polynomial, but `Provenance` needs the
+    // parent and combination as well, so the generations are dropped and `provenance` is empty.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).map_err(|source| Error::io(path, source))?;
+        let mut start = [0u8; 12];
+        let read = file
+            .read(&mut start)
+            .map_err(|source| Error::io(path, source))?;
+        let start = &start[..read];
+        let polynomials = if start.starts_with(&export::BINARY_MAGIC) {
+            Some(export::read_binary(path)?)
+        } else if export::CSV_HEADER_STARTS
+            .iter()
+            .any(|header| start.starts_with(header.as_bytes()))
+        {
+            Some(export::read_csv(path)?)
+        } else {
+            None
+        };
+        if let Some(polynomials) = polynomials {
+            let polynomials = polynomials
+                .into_iter()
+                .map(|(polynomial, _)| polynomial)
+                .collect();
+            return Ok(Output::new(polynomials, Tolerance::default()));
+        }
+        file.rewind().map_err(|source| Error::io(path, source))?;
+        let output: Output =
+            serde_json::from_reader(file).map_err(|source| Error::json(path, source))?;
+        if output.version > OUTPUT_VERSION {
+            return Err(Error::InvalidOutput(format!(
+                "{} has format version {} but this program only reads up to {}",
+                path.display(),
+                output.version,
+                OUTPUT_VERSION
+            )));
+        }
+        Ok(output)
+    }
+}
+
+// The parameters of the run that found the polynomials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub matrix_size: usize,
+    pub polynomial_length: usize,
+    pub mutated_polynomials_to_evaluate: usize,
+    pub matrices_to_fuzz: usize,
+    // The legacy results file the run was imported from.
+    #[serde(default)]
+    pub imported_from: Option<PathBuf>,
+}
+
+// How a polynomial was found: `combination` of the coefficients of `parent` was minimized in
+// `generation`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub parent: Polynomial,
+    pub combination: Vec<usize>,
+    pub generation: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationReport {
+    pub generation: usize,
+    // Time spent on the generation, summed over resumed runs.
+    pub elapsed_seconds: f64,
+    // Interesting polynomials kept at the end of the generation.
+    pub interesting_polynomials: usize,
+}
+
+// One part of the fuzzed matrix sample.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SampleDescription {
+    pub name: String,
+    pub description: String,
+    pub matrices: usize,
+}
+
+pub fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::OutputFormat;
+    use std::fs;
+
+    fn output() -> Output {
+        let polynomials = vec![
+            Polynomial::from_vec(vec![1.0, -0.25, 0.0, 1e-9, 2.0], 2),
+            Polynomial::from_vec(vec![0.5, 1.0, 3.0], 3),
+        ];
+        Output::new(polynomials, Tolerance::default())
+    }
+
+    fn coefficients(output: &Output) -> Vec<(Vec<f64>, usize)> {
+        output
+            .interesting_polynomials
+            .iter()
+            .map(|polynomial| {
+                let mut coefficients = polynomial.coefficients().to_vec();
+                // CSV and binary pad shorter polynomials with leading zeros.
+                while coefficients.len() > 1 && coefficients[0] == 0.0 {
+                    coefficients.remove(0);
+                }
+                (coefficients, polynomial.get_size())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loads_every_format() {
+        let directory = std::env::temp_dir().join(format!(
+            "matrix_polynomial_analysis-output-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        let output = output();
+        for format in [OutputFormat::Json, OutputFormat::Csv, OutputFormat::Binary] {
+            // The extension is not used to tell the format.
+            let path = directory.join(format!("output-{}.txt", format));
+            export::write_output(&path, &output, format).unwrap();
+            let loaded = Output::load(&path).unwrap();
+            assert_eq!(coefficients(&loaded), coefficients(&output), "{}", format);
+        }
+
+        let empty = Output::new(Vec::new(), Tolerance::default());
+        let path = directory.join("empty.csv");
+        export::write_output(&path, &empty, OutputFormat::Csv).unwrap();
+        assert!(Output::load(&path)
+            .unwrap()
+            .interesting_polynomials
+            .is_empty());
+
+        let path = directory.join("broken.csv");
+        fs::write(&path, "x^1,x^0,matrix_size\n1,oops,2\n").unwrap();
+        assert!(matches!(Output::load(&path), Err(Error::InvalidOutput(_))));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_binary_fields_that_do_not_fit() {
+        let directory = std::env::temp_dir().join(format!(
+            "matrix_polynomial_analysis-output-binary-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+
+        // A header asking for u32::MAX terms in a file of a few bytes.
+        let path = directory.join("damaged.bin");
+        let mut bytes = export::BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&export::BINARY_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(Output::load(&path), Err(Error::InvalidOutput(_))));
+
+        // The generation would be truncated to a u32.
+        let mut output = output();
+        let provenance = |generation| {
+            Some(Provenance {
+                parent: output.interesting_polynomials[0].clone(),
+                combination: vec![1],
+                generation,
+            })
+        };
+        output.provenance = vec![provenance(3), provenance(u32::MAX as usize)];
+        let path = directory.join("generation.bin");
+        assert!(matches!(
+            export::write_output(&path, &output, OutputFormat::Binary),
+            Err(Error::InvalidOutput(_))
+        ));
+        output.provenance.pop();
+        export::write_output(&path, &output, OutputFormat::Binary).unwrap();
+        let read = export::read_binary(&path).unwrap();
+        assert_eq!(read[0].1, Some(3));
+        assert_eq!(read[1].1, None);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
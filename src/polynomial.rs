@@ -1,7 +1,10 @@
+use crate::error::Error;
+use crate::tolerance::Tolerance;
 use nalgebra::DMatrix;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::ops::{Index, IndexMut};
+use std::fmt;
+use std::ops::{Add, Index, IndexMut, Mul, Sub};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Polynomial {
@@ -10,20 +13,6 @@ pub struct Polynomial {
 }
 
 impl Polynomial {
-    pub fn to_string(&self) -> String {
-        let mut i = self.len();
-        let mut out: String = String::new();
-        for term in self.coefficients.iter() {
-            i -= 1;
-            if term >= &0.0 {
-                out = format!("{}+ {:.7}x^{} ", out, term, i);
-            } else {
-                out = format!("{}- {:.7}x^{} ", out, term.abs(), i);
-            }
-        }
-        out
-    }
-
     pub fn is_polynomial_nonnegative(&self) -> bool {
         self.is_polynomial_nonnegative_with_threshold(0.0)
     }
@@ -37,37 +26,40 @@ impl Polynomial {
         true
     }
 
-    pub fn is_polynomial_nonnegative_from_matrix(&self, matrix: &DMatrix<f64>) -> bool {
+    pub fn is_polynomial_nonnegative_from_matrix(
+        &self,
+        matrix: &DMatrix<f64>,
+        tolerance: &Tolerance,
+    ) -> bool {
+        tolerance.is_matrix_nonnegative(&self.evaluate_matrix(matrix))
+    }
+
+    pub fn is_polynomial_nonnegative_from_matrix_with_powers(
+        &self,
+        matrix_powers: &[DMatrix<f64>],
+        tolerance: &Tolerance,
+    ) -> bool {
+        tolerance.is_matrix_nonnegative(&self.evaluate_matrix_with_powers(matrix_powers))
+    }
+
+    // Computes p(A) for the given matrix.
+    pub fn evaluate_matrix(&self, matrix: &DMatrix<f64>) -> DMatrix<f64> {
         let mut final_matrix = matrix.scale(0.0);
-        let mut working_matrix = DMatrix::<f64>::identity(self.size, self.size);
+        let mut working_matrix = DMatrix::<f64>::identity(matrix.nrows(), matrix.ncols());
         for coefficient in self.coefficients.iter().rev() {
             final_matrix += working_matrix.scale(*coefficient);
             working_matrix *= matrix;
         }
-
-        for value in final_matrix.iter().enumerate() {
-            if value.1 < &0.0 {
-                return false;
-            }
-        }
-        true
+        final_matrix
     }
 
-    pub fn is_polynomial_nonnegative_from_matrix_with_powers(
-        &self,
-        matrix_powers: &Vec<DMatrix<f64>>,
-    ) -> bool {
+    // Computes p(A) from precomputed powers [I, A, A^2, ...] of a matrix.
+    pub fn evaluate_matrix_with_powers(&self, matrix_powers: &[DMatrix<f64>]) -> DMatrix<f64> {
         let mut final_matrix = matrix_powers[0].scale(0.0);
         for (coefficient, matrix_power) in self.coefficients.iter().rev().zip(matrix_powers) {
             final_matrix += matrix_power.scale(*coefficient);
         }
-
-        for value in final_matrix.iter().enumerate() {
-            if value.1 < &0.0 {
-                return false;
-            }
-        }
-        true
+        final_matrix
     }
 
     pub fn from_element(polynomial_length: usize, matrix_size: usize, element: f64) -> Polynomial {
@@ -79,15 +71,67 @@ impl Polynomial {
 
     pub fn from_vec(coefficients: Vec<f64>, matrix_size: usize) -> Polynomial {
         Polynomial {
-            coefficients: coefficients,
+            coefficients,
             size: matrix_size,
         }
     }
 
+    // Parses the `Display` format, e.g. `+ 1.0000000x^4 - 0.2000000x^2 + 1.0000000x^0`, as well
+    // as hand written forms like `x^2 - 0.3x + 1` or `2*x^3 + x`. Terms may come in any order,
+    // missing powers are zero and repeated powers are added up.
+    pub fn parse(text: &str, matrix_size: usize) -> Result<Polynomial, Error> {
+        Self::parse_coefficients(text)
+            .map(|coefficients| Polynomial::from_vec(coefficients, matrix_size))
+    }
+
+    // The coefficients of a polynomial in any format `parse` reads, largest power first. The text
+    // does not say which matrix size the polynomial is for.
+    pub fn parse_coefficients(text: &str) -> Result<Vec<f64>, Error> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if text.is_empty() {
+            return Err(Error::InvalidPolynomial(String::from("no terms")));
+        }
+
+        // Split before every sign that is not part of a number's exponent.
+        let mut terms = Vec::new();
+        let mut start = 0;
+        let characters: Vec<char> = text.chars().collect();
+        for (i, c) in characters.iter().enumerate() {
+            let exponent_sign = i > 1
+                && matches!(characters[i - 1], 'e' | 'E')
+                && characters[i - 2].is_ascii_digit();
+            if (*c == '+' || *c == '-') && i > start && !exponent_sign {
+                terms.push(&text[start..i]);
+                start = i;
+            }
+        }
+        terms.push(&text[start..]);
+
+        let mut powers: Vec<(usize, f64)> = Vec::new();
+        for term in terms {
+            powers.push(parse_term(term)?);
+        }
+        let degree = powers.iter().map(|(power, _)| *power).max().unwrap_or(0);
+        let mut coefficients = vec![0.0; degree + 1];
+        for (power, coefficient) in powers {
+            coefficients[degree - power] += coefficient;
+        }
+        Ok(coefficients)
+    }
+
     pub fn len(&self) -> usize {
         self.coefficients.len()
     }
 
+    // The coefficients, largest power first.
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
     pub fn min_term(&self) -> f64 {
         let mut min = self.coefficients[0].abs();
         for coefficient in &self.coefficients {
@@ -108,7 +152,7 @@ impl Polynomial {
         max
     }
 
-    pub fn are_first_last_negative(&self) -> bool {
+    pub fn are_first_last_negative(&self, tolerance: &Tolerance) -> bool {
         for i in 0..self.size {
             if self.len() - 1 < i {
                 break;
@@ -118,21 +162,18 @@ impl Polynomial {
             if self[i] < 0.0 || self[last_term] < 0.0 {
                 return true;
             }
-            while approx_equal(self[last_term], 0.0) {
+            // Coefficients within `zero_coefficient` of zero are skipped, a size apart.
+            while tolerance.is_zero(self[last_term]) {
                 if self[last_term] < 0.0 {
                     return true;
-                } else if self[last_term] > 0.0000001 {
-                    break;
                 } else if last_term < self.size {
                     break;
                 }
                 last_term -= self.size;
             }
-            while approx_equal(self[term], 0.0) {
+            while tolerance.is_zero(self[term]) {
                 if self[term] < 0.0 {
                     return true;
-                } else if self[term] > 0.0000001 {
-                    break;
                 } else if term + self.size >= self.len() {
                     break;
                 }
@@ -158,14 +199,82 @@ impl Polynomial {
         derivative
     }
 
+    // The highest power with a nonzero coefficient, None for the zero polynomial.
+    pub fn degree(&self) -> Option<usize> {
+        let leading = self.coefficients.iter().position(|term| *term != 0.0)?;
+        Some(self.len() - 1 - leading)
+    }
+
+    // Computes p(x) with Horner's method.
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .fold(0.0, |value, coefficient| value * x + coefficient)
+    }
+
+    // Computes p(q(x)) with Horner's method. The result keeps the matrix size of `self`.
+    pub fn compose(&self, inner: &Polynomial) -> Polynomial {
+        let mut composition = Polynomial::from_vec(Vec::new(), self.size);
+        for coefficient in &self.coefficients {
+            composition =
+                &(&composition * inner) + &Polynomial::from_vec(vec![*coefficient], self.size);
+        }
+        composition
+    }
+
+    // Polynomial long division, returns (quotient, remainder) with p = quotient * divisor +
+    // remainder and the remainder of lower degree than the divisor. None when the divisor is zero.
+    pub fn div_rem(&self, divisor: &Polynomial) -> Option<(Polynomial, Polynomial)> {
+        let divisor_degree = divisor.degree()?;
+        let divisor_terms = &divisor.coefficients[divisor.len() - 1 - divisor_degree..];
+        let mut remainder = self.coefficients.clone();
+        if remainder.len() <= divisor_degree {
+            return Some((
+                Polynomial::from_vec(vec![0.0], self.size),
+                Polynomial::from_vec(remainder, self.size),
+            ));
+        }
+
+        let quotient_terms = remainder.len() - divisor_degree;
+        let mut quotient = vec![0.0; quotient_terms];
+        for i in 0..quotient_terms {
+            let factor = remainder[i] / divisor_terms[0];
+            quotient[i] = factor;
+            for (j, term) in divisor_terms.iter().enumerate() {
+                remainder[i + j] -= factor * term;
+            }
+        }
+        let remainder = remainder.split_off(quotient_terms);
+        let remainder = if remainder.is_empty() {
+            vec![0.0]
+        } else {
+            remainder
+        };
+        Some((
+            Polynomial::from_vec(quotient, self.size),
+            Polynomial::from_vec(remainder, self.size),
+        ))
+    }
+
     // Returns a subset of the vector containing the elementwise smallest polynomials.
-    pub fn collapse_polynomials(polynomial_base: &Vec<Polynomial>) -> Vec<Polynomial> {
-        let mut polynomials = polynomial_base.clone();
+    pub fn collapse_polynomials(polynomial_base: &[Polynomial]) -> Vec<Polynomial> {
+        Polynomial::collapse_polynomials_indexed(polynomial_base)
+            .into_iter()
+            .map(|(_, polynomial)| polynomial)
+            .collect()
+    }
+
+    // Same as `collapse_polynomials`, with the index in `polynomial_base` of every polynomial kept.
+    pub fn collapse_polynomials_indexed(
+        polynomial_base: &[Polynomial],
+    ) -> Vec<(usize, Polynomial)> {
+        let mut polynomials = polynomial_base.to_vec();
+        let mut indices: Vec<usize> = (0..polynomials.len()).collect();
         // Scale down polynomials so that their largest element is one.
-        for i in 0..polynomials.len() {
-            let largest_value = polynomials[i].max_term().abs();
-            for j in 0..polynomials[i].len() {
-                polynomials[i][j] /= largest_value;
+        for polynomial in polynomials.iter_mut() {
+            let largest_value = polynomial.max_term().abs();
+            for coefficient in polynomial.coefficients.iter_mut() {
+                *coefficient /= largest_value;
             }
         }
 
@@ -180,15 +289,14 @@ impl Polynomial {
                         break;
                     };
                 }
-                let mut bool_is_smaller_polynomial = true;
-                for k in 0..polynomials[i].len() {
-                    if polynomials[i][k] > polynomials[j][k] {
-                        bool_is_smaller_polynomial = false;
-                        break;
-                    }
-                }
+                let bool_is_smaller_polynomial = polynomials[i]
+                    .coefficients
+                    .iter()
+                    .zip(polynomials[j].coefficients.iter())
+                    .all(|(smaller, larger)| smaller <= larger);
                 if bool_is_smaller_polynomial {
                     polynomials.remove(j);
+                    indices.remove(j);
                     was_removed = true;
                     break;
                 } else {
@@ -199,13 +307,62 @@ impl Polynomial {
                 i += 1;
             }
         }
-        polynomials
+        indices.into_iter().zip(polynomials).collect()
+    }
+}
+
+pub fn approx_equal(term1: f64, term2: f64, epsilon: f64) -> bool {
+    (term1 - term2).abs() < epsilon
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut i = self.len();
+        for term in self.coefficients.iter() {
+            i -= 1;
+            if term >= &0.0 {
+                write!(f, "+ {:.7}x^{} ", term, i)?;
+            } else {
+                write!(f, "- {:.7}x^{} ", term.abs(), i)?;
+            }
+        }
+        Ok(())
     }
 }
 
-// TODO this is a pretty rough function, for now my percision caps at 3 decimals so it is sufficient.
-pub fn approx_equal(term1: f64, term2: f64) -> bool {
-    (term1 - term2).abs() < 0.00001
+// Parses one signed term such as `-0.3x`, `+x^2` or `1.5`, returning its power and coefficient.
+fn parse_term(term: &str) -> Result<(usize, f64), Error> {
+    let invalid = || Error::InvalidPolynomial(format!("can not read the term \"{}\"", term));
+    let (sign, body) = match term.as_bytes().first() {
+        Some(b'-') => (-1.0, &term[1..]),
+        Some(b'+') => (1.0, &term[1..]),
+        _ => (1.0, term),
+    };
+    if body.is_empty() {
+        return Err(invalid());
+    }
+    let (coefficient, power) = match body.split_once('x') {
+        Some((coefficient, power)) => {
+            let coefficient = coefficient.strip_suffix('*').unwrap_or(coefficient);
+            let power = match power {
+                "" => 1,
+                power => power
+                    .strip_prefix('^')
+                    .and_then(|power| power.parse().ok())
+                    .ok_or_else(invalid)?,
+            };
+            (coefficient, power)
+        }
+        None => (body, 0),
+    };
+    let coefficient: f64 = match coefficient {
+        "" => 1.0,
+        coefficient => coefficient.parse().map_err(|_| invalid())?,
+    };
+    if !coefficient.is_finite() {
+        return Err(invalid());
+    }
+    Ok((power, sign * coefficient))
 }
 
 impl PartialEq for Polynomial {
@@ -226,19 +383,7 @@ impl Eq for Polynomial {}
 
 impl PartialOrd for Polynomial {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.size > other.size {
-            return Some(Ordering::Greater);
-        } else if self.size < other.size {
-            return Some(Ordering::Less);
-        }
-        for i in 0..self.size {
-            if self[i] > other[i] {
-                return Some(Ordering::Greater);
-            } else if self[i] < other[i] {
-                return Some(Ordering::Less);
-            }
-        }
-        Some(Ordering::Equal)
+        Some(self.cmp(other))
     }
 }
 
@@ -262,13 +407,269 @@ impl Ord for Polynomial {
 
 impl Index<usize> for Polynomial {
     type Output = f64;
-    fn index<'a>(&'a self, i: usize) -> &'a f64 {
+    fn index(&self, i: usize) -> &f64 {
         &self.coefficients[i]
     }
 }
 
 impl IndexMut<usize> for Polynomial {
-    fn index_mut<'a>(&'a mut self, i: usize) -> &'a mut f64 {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
         &mut self.coefficients[i]
     }
 }
+
+// The coefficients of `polynomial` padded with leading zeros to `length` terms.
+fn padded(polynomial: &Polynomial, length: usize) -> impl Iterator<Item = f64> + '_ {
+    std::iter::repeat_n(0.0, length - polynomial.len())
+        .chain(polynomial.coefficients.iter().copied())
+}
+
+// Adds term by term, aligned at the constant term. Like the other operators the result keeps the
+// matrix size of the left operand.
+impl Add for &Polynomial {
+    type Output = Polynomial;
+    fn add(self, other: &Polynomial) -> Polynomial {
+        let length = self.len().max(other.len());
+        let coefficients = padded(self, length)
+            .zip(padded(other, length))
+            .map(|(a, b)| a + b)
+            .collect();
+        Polynomial::from_vec(coefficients, self.size)
+    }
+}
+
+impl Add for Polynomial {
+    type Output = Polynomial;
+    fn add(self, other: Polynomial) -> Polynomial {
+        &self + &other
+    }
+}
+
+impl Sub for &Polynomial {
+    type Output = Polynomial;
+    fn sub(self, other: &Polynomial) -> Polynomial {
+        let length = self.len().max(other.len());
+        let coefficients = padded(self, length)
+            .zip(padded(other, length))
+            .map(|(a, b)| a - b)
+            .collect();
+        Polynomial::from_vec(coefficients, self.size)
+    }
+}
+
+impl Sub for Polynomial {
+    type Output = Polynomial;
+    fn sub(self, other: Polynomial) -> Polynomial {
+        &self - &other
+    }
+}
+
+// The product of two polynomials, the convolution of their coefficients.
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        if self.is_empty() || other.is_empty() {
+            return Polynomial::from_vec(Vec::new(), self.size);
+        }
+        let mut coefficients = vec![0.0; self.len() + other.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] += a * b;
+            }
+        }
+        Polynomial::from_vec(coefficients, self.size)
+    }
+}
+
+impl Mul for Polynomial {
+    type Output = Polynomial;
+    fn mul(self, other: Polynomial) -> Polynomial {
+        &self * &other
+    }
+}
+
+impl Mul<f64> for &Polynomial {
+    type Output = Polynomial;
+    fn mul(self, scalar: f64) -> Polynomial {
+        let coefficients = self.coefficients.iter().map(|term| term * scalar).collect();
+        Polynomial::from_vec(coefficients, self.size)
+    }
+}
+
+impl Mul<f64> for Polynomial {
+    type Output = Polynomial;
+    fn mul(self, scalar: f64) -> Polynomial {
+        &self * scalar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The sizes stay at 0 to check that nothing but the terms is used.
+    fn polynomial(coefficients: &[f64]) -> Polynomial {
+        Polynomial::from_vec(coefficients.to_vec(), 0)
+    }
+
+    #[test]
+    fn adds_and_subtracts_aligned_at_the_constant_term() {
+        let p = polynomial(&[1.0, 2.0, 3.0]);
+        let q = polynomial(&[4.0, 5.0]);
+        assert_eq!((&p + &q).coefficients(), &[1.0, 6.0, 8.0]);
+        assert_eq!((&q + &p).coefficients(), &[1.0, 6.0, 8.0]);
+        assert_eq!((p.clone() - q.clone()).coefficients(), &[1.0, -2.0, -2.0]);
+        assert_eq!((q - p).coefficients(), &[-1.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn multiplies() {
+        // (x + 1)(x - 1) = x^2 - 1
+        let p = polynomial(&[1.0, 1.0]);
+        let q = polynomial(&[1.0, -1.0]);
+        assert_eq!((&p * &q).coefficients(), &[1.0, 0.0, -1.0]);
+        assert_eq!((p.clone() * 2.0).coefficients(), &[2.0, 2.0]);
+        assert!((p * polynomial(&[])).is_empty());
+    }
+
+    #[test]
+    fn composes() {
+        // p(x) = x^2 + 1 and q(x) = x - 1 give p(q(x)) = x^2 - 2x + 2.
+        let p = Polynomial::from_vec(vec![1.0, 0.0, 1.0], 3);
+        let q = polynomial(&[1.0, -1.0]);
+        let composition = p.compose(&q);
+        assert_eq!(composition.coefficients(), &[1.0, -2.0, 2.0]);
+        assert_eq!(composition.get_size(), 3);
+    }
+
+    #[test]
+    fn divides_with_remainder() {
+        // x^3 - 2x^2 + 3 = (x^2 - x - 1)(x - 1) + 2, with a leading zero on the divisor.
+        let p = polynomial(&[1.0, -2.0, 0.0, 3.0]);
+        let divisor = polynomial(&[0.0, 1.0, -1.0]);
+        let (quotient, remainder) = p.div_rem(&divisor).unwrap();
+        assert_eq!(quotient.coefficients(), &[1.0, -1.0, -1.0]);
+        assert_eq!(remainder.coefficients(), &[2.0]);
+
+        // A divisor of higher degree leaves everything in the remainder.
+        let (quotient, remainder) = divisor.div_rem(&p).unwrap();
+        assert_eq!(quotient.coefficients(), &[0.0]);
+        assert_eq!(remainder.coefficients(), divisor.coefficients());
+
+        assert!(p.div_rem(&polynomial(&[0.0, 0.0])).is_none());
+        assert_eq!(polynomial(&[0.0, 2.0, 0.0]).degree(), Some(1));
+        assert_eq!(polynomial(&[0.0]).degree(), None);
+    }
+
+    #[test]
+    fn evaluates() {
+        let p = polynomial(&[2.0, -3.0, 1.0]);
+        assert_eq!(p.evaluate(0.0), 1.0);
+        assert_eq!(p.evaluate(1.0), 0.0);
+        assert_eq!(p.evaluate(3.0), 10.0);
+        assert_eq!(polynomial(&[]).evaluate(3.0), 0.0);
+    }
+
+    #[test]
+    fn evaluates_matrices_of_any_size() {
+        // p(x) = x^2 - 2x + 3 at an upper triangular A = [[1, 2], [0, 3]].
+        let p = polynomial(&[1.0, -2.0, 3.0]);
+        let a = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 0.0, 3.0]);
+        let expected = DMatrix::from_row_slice(2, 2, &[2.0, 4.0, 0.0, 6.0]);
+        assert_eq!(p.evaluate_matrix(&a), expected);
+
+        let powers = vec![DMatrix::identity(2, 2), a.clone(), &a * &a];
+        assert_eq!(p.evaluate_matrix_with_powers(&powers), expected);
+
+        let b = DMatrix::from_element(1, 1, 3.0);
+        assert_eq!(p.evaluate_matrix(&b), DMatrix::from_element(1, 1, 6.0));
+    }
+
+    fn parsed(text: &str) -> Vec<f64> {
+        Polynomial::parse(text, 2).unwrap().coefficients().to_vec()
+    }
+
+    #[test]
+    fn parses_what_it_prints() {
+        for coefficients in [
+            vec![-0.0069728, 0.4549117, -1.0, 0.0, 2.5],
+            vec![1.0, 1.0, 1.0, 1.0, 1.0],
+            vec![0.0, -3.25, 0.0000001],
+            vec![7.0],
+        ] {
+            let polynomial = Polynomial::from_vec(coefficients.clone(), 3);
+            let text = polynomial.to_string();
+            let read = Polynomial::parse(&text, 3).unwrap();
+            assert_eq!(read.get_size(), 3);
+            assert_eq!(read.coefficients(), &coefficients[..], "{}", text);
+            assert_eq!(read.to_string(), text);
+        }
+        assert_eq!(
+            parsed("+ 1.0000000x^4 - 0.2000000x^2 + 1.0000000x^0"),
+            vec![1.0, 0.0, -0.2, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn parses_hand_written_polynomials() {
+        assert_eq!(parsed("x^2 - 0.3x + 1"), vec![1.0, -0.3, 1.0]);
+        // Implicit coefficients and powers, and a `*` between coefficient and x.
+        assert_eq!(parsed("-x^3 + x - 1"), vec![-1.0, 0.0, 1.0, -1.0]);
+        assert_eq!(parsed("2*x^3+x"), vec![2.0, 0.0, 1.0, 0.0]);
+        // Any order, missing powers are zero and repeated powers add up.
+        assert_eq!(parsed("1 + x^4 - 0.5x^2"), vec![1.0, 0.0, -0.5, 0.0, 1.0]);
+        assert_eq!(parsed("x + x + 3"), vec![2.0, 3.0]);
+        // Signs of exponents are not term separators.
+        assert_eq!(parsed("1e-3x^2 + 2.5E+1"), vec![0.001, 0.0, 25.0]);
+        assert_eq!(parsed("  - 4  "), vec![-4.0]);
+    }
+
+    #[test]
+    fn rejects_malformed_polynomials() {
+        for text in [
+            "", "   ", "x^", "x^-1", "y^2", "1 +", "x^2 ++ 1", "2x^1.5", "inf",
+        ] {
+            assert!(
+                matches!(Polynomial::parse(text, 2), Err(Error::InvalidPolynomial(_))),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn first_and_last_terms_skip_zero_coefficients() {
+        let tolerance = Tolerance::default();
+        let first_last_negative = |coefficients: &[f64], tolerance: &Tolerance| -> bool {
+            Polynomial::from_vec(coefficients.to_vec(), 2).are_first_last_negative(tolerance)
+        };
+        assert!(!first_last_negative(
+            &[1.0, 0.0, -1.0, 0.0, 1.0],
+            &tolerance
+        ));
+        assert!(first_last_negative(&[1.0, 0.0, 1.0, 0.0, -1.0], &tolerance));
+        assert!(first_last_negative(&[1.0, -1.0, 1.0, 0.0, 1.0], &tolerance));
+        // The constant term is below `zero_coefficient`, so the x^2 term is checked as well.
+        let small = [1.0, 0.0, -1e-6, 0.0, 5e-6];
+        assert!(first_last_negative(&small, &tolerance));
+        let strict = Tolerance {
+            zero_coefficient: 1e-6,
+            ..tolerance
+        };
+        assert!(!first_last_negative(&small, &strict));
+    }
+
+    #[test]
+    fn parsed_polynomials_keep_their_size() {
+        assert_eq!(
+            Polynomial::parse_coefficients("x^2 - 0.3x + 1").unwrap(),
+            vec![1.0, -0.3, 1.0]
+        );
+        // Polynomials are compared by their first `size` coefficients.
+        let first = Polynomial::parse("x^2 - 0.3x + 1", 2).unwrap();
+        let second = Polynomial::parse("x^2 + 0.3x + 1", 2).unwrap();
+        assert_eq!(first.get_size(), 2);
+        assert_ne!(first, second);
+        assert!(first < second);
+    }
+}
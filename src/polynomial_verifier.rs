@@ -1,106 +1,786 @@
+use crate::error::{Error, Result};
+use crate::exact::{self, Certificate};
+use crate::matrix_family::{self, MatrixFamily};
+use crate::matrix_search;
 use crate::polynomial::Polynomial;
-use crate::matrix_generator;
-use log::{info, trace};
+use crate::power_table::PowerTable;
+use crate::tolerance::Tolerance;
+use crate::zero_pattern;
+use log::{debug, info, trace};
 use nalgebra::DMatrix;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 
+// How far `max_reduction` stays below the exact limit of the sample, relative to the reduction.
+// Leaves every entry of p(A) this fraction of its slope above the allowance, well above the
+// rounding error of evaluating p(A).
+const REDUCTION_MARGIN: f64 = 1e-9;
+
+// The stage of `PolynomialVerifier::verify` that decided the verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Check {
+    NonnegativeCoefficients,
+    FirstLastNegative,
+    SimpleMatrices,
+    FuzzedMatrices,
+    MatrixSearch,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Check::NonnegativeCoefficients => "all coefficients are nonnegative",
+            Check::FirstLastNegative => "first or last terms are negative",
+            Check::SimpleMatrices => "identity and permutation matrices",
+            Check::FuzzedMatrices => "fuzzed matrices",
+            Check::MatrixSearch => "local search over nonnegative matrices",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+// A nonnegative matrix A where p(A) has a negative entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Counterexample {
+    pub matrix: DMatrix<f64>,
+    pub result: DMatrix<f64>,
+    pub min_entry: f64,
+    pub min_index: (usize, usize),
+    // The matrix family a fuzzed counterexample was drawn from.
+    #[serde(default)]
+    pub family: Option<String>,
+    // The exact value of p(A) when verifying with exact arithmetic.
+    #[serde(default)]
+    pub certificate: Option<Certificate>,
+}
+
+impl Counterexample {
+    pub fn new(polynomial: &Polynomial, matrix: DMatrix<f64>) -> Self {
+        let result = polynomial.evaluate_matrix(&matrix);
+        Counterexample::from_result(matrix, result)
+    }
+
+    // Builds the counterexample from the precomputed powers [I, A, A^2, ...] of A.
+    pub fn from_powers(polynomial: &Polynomial, matrix_powers: &[DMatrix<f64>]) -> Self {
+        let result = polynomial.evaluate_matrix_with_powers(matrix_powers);
+        let matrix = matrix_powers.get(1).unwrap_or(&matrix_powers[0]).clone();
+        Counterexample::from_result(matrix, result)
+    }
+
+    fn from_result(matrix: DMatrix<f64>, result: DMatrix<f64>) -> Self {
+        let (min_index, min_entry) = result.iter().enumerate().fold(
+            (0, f64::INFINITY),
+            |(min_index, min_entry), (index, value)| {
+                if *value < min_entry {
+                    (index, *value)
+                } else {
+                    (min_index, min_entry)
+                }
+            },
+        );
+        // nalgebra stores matrices in column-major order.
+        let min_index = (min_index % result.nrows(), min_index / result.nrows());
+        Counterexample {
+            matrix,
+            result,
+            min_entry,
+            min_index,
+            family: None,
+            certificate: None,
+        }
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(family) = &self.family {
+            writeln!(f, "Found in the {} matrix family", family)?;
+        }
+        write!(
+            f,
+            "A ={}p(A) ={}smallest entry {} at row {}, column {}",
+            self.matrix, self.result, self.min_entry, self.min_index.0, self.min_index.1
+        )?;
+        if let Some(certificate) = &self.certificate {
+            write!(f, "\nCertified with {}", certificate)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Verdict {
+    pub preserves: bool,
+    pub check: Check,
+    pub counterexample: Option<Counterexample>,
+}
+
+impl Verdict {
+    fn preserved(check: Check) -> Self {
+        Verdict {
+            preserves: true,
+            check,
+            counterexample: None,
+        }
+    }
+
+    fn rejected(check: Check, counterexample: Option<Counterexample>) -> Self {
+        Verdict {
+            preserves: false,
+            check,
+            counterexample,
+        }
+    }
+}
+
+// Describes the matrices a `PolynomialVerifier` tests polynomials against.
+pub struct VerifierConfig {
+    pub matrices_to_fuzz: usize,
+    pub families: Vec<Box<dyn MatrixFamily>>,
+    // Weighted matrices drawn for every zero pattern, zero disables the sweep.
+    pub zero_pattern_samples: usize,
+    // Recheck float failures in exact rational arithmetic before rejecting a polynomial.
+    pub exact_arithmetic: bool,
+    pub tolerance: Tolerance,
+    pub seed: u64,
+    // Threads in the worker pool, zero uses every core.
+    pub workers: usize,
+    // Finish every verification with a local search for a violating matrix.
+    pub matrix_search: bool,
+    pub matrix_search_restarts: usize,
+}
+
+// A pool with `workers` threads, or one per core when `workers` is zero.
+pub fn worker_pool(workers: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .thread_name(|index| format!("worker-{}", index))
+        .build()
+        .expect("worker threads should start")
+}
+
+// The fuzzed matrices of a verifier and the family each was drawn from, stored with a run so it
+// can be resumed against exactly the same sample.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzedMatrices {
+    pub matrix_size: usize,
+    pub families: Vec<String>,
+    pub matrices: Vec<DMatrix<f64>>,
+}
+
+// Holds the fuzzed matrices and the worker pool used for every job of a run.
 #[derive(Debug, Clone)]
 pub struct PolynomialVerifier {
-    matrices: Vec<Vec<DMatrix<f64>>>,
+    matrices: PowerTable,
+    // Name of the family each matrix in `matrices` was drawn from.
+    matrix_families: Vec<&'static str>,
+    exact_arithmetic: bool,
+    tolerance: Tolerance,
+    pool: Arc<ThreadPool>,
+    matrix_search: bool,
+    matrix_search_restarts: usize,
+    // Every local search starts from this seed, so its result only depends on the polynomial.
+    search_seed: u64,
+    // Polynomials verified so far, shared by the clones of the verifier.
+    verifications: Arc<AtomicU64>,
 }
 
 impl PolynomialVerifier {
-    pub fn new(number_of_matrices_to_verify: usize, matrix_size: usize, powers: usize) -> Self {
-        let mut matrices = Vec::new();
-
+    // The matrices to fuzz are split evenly between the configured families. Zero pattern matrices
+    // come first since they are the most likely to break a polynomial.
+    pub fn new<R: RngCore>(
+        config: &VerifierConfig,
+        matrix_size: usize,
+        powers: usize,
+        rng: &mut R,
+    ) -> Self {
         let start = Instant::now();
-        matrices.append(&mut matrix_generator::generate_circulant_matrices(
-            number_of_matrices_to_verify,
-            matrix_size,
-            powers,
-        ));
-
-        /*
-        matrices.append(&mut PolynomialVerifier::generate_random_matrices(
-            number_of_matrices_to_verify,
-            matrix_size,
-        ));
-        */
+        let patterns = if config.zero_pattern_samples > 0 {
+            zero_pattern::zero_patterns(matrix_size, rng)
+        } else {
+            Vec::new()
+        };
+        let matrices_per_family = config.matrices_to_fuzz / config.families.len().max(1);
+        let number_of_matrices = patterns.len() * config.zero_pattern_samples
+            + matrices_per_family * config.families.len();
+        let mut matrices = PowerTable::new(matrix_size, powers, number_of_matrices);
+        let mut matrix_families = Vec::with_capacity(number_of_matrices);
+
+        if !patterns.is_empty() {
+            info!("Sweeping {} zero patterns", patterns.len());
+        }
+        for pattern in patterns {
+            for _ in 0..config.zero_pattern_samples {
+                matrices.set_matrix(matrix_families.len(), &pattern.weighted_matrix(rng));
+                matrix_families.push("zero_pattern");
+            }
+        }
+
+        for family in &config.families {
+            for _ in 0..matrices_per_family {
+                matrices.set_matrix(matrix_families.len(), &family.generate(matrix_size, rng));
+                matrix_families.push(family.name());
+            }
+        }
+
         let duration = start.elapsed();
         info!("Generated matrices in {:?}", duration);
-        PolynomialVerifier { matrices }
+        Self::with_matrices(config, matrices, matrix_families)
     }
 
-    // TODO throwing in random zeros is useful this also scales poorly with size of matrix revist this function with a better plan to add zeros.
-    /*
-    pub fn test_polynomial_with_zeros(&self, polynomial: &Polynomial) -> bool {
-        if polynomial.is_polynomial_nonnegative() {
-            return true;
+    // A verifier over matrices stored by `fuzzed_matrices`, ignoring how `config` would draw them.
+    pub fn from_fuzzed_matrices(
+        config: &VerifierConfig,
+        fuzzed_matrices: &FuzzedMatrices,
+        powers: usize,
+    ) -> Result<Self> {
+        if fuzzed_matrices.families.len() != fuzzed_matrices.matrices.len() {
+            return Err(Error::InvalidState(format!(
+                "the stored sample has {} matrices but {} family names",
+                fuzzed_matrices.matrices.len(),
+                fuzzed_matrices.families.len()
+            )));
         }
-        if polynomial.are_first_last_negative() {
-            return false;
+        let size = fuzzed_matrices.matrix_size;
+        if let Some(matrix) = fuzzed_matrices
+            .matrices
+            .iter()
+            .find(|matrix| matrix.shape() != (size, size))
+        {
+            return Err(Error::InvalidState(format!(
+                "the stored sample has a {}-by-{} matrix in a sample of {}-by-{} matrices",
+                matrix.nrows(),
+                matrix.ncols(),
+                size,
+                size
+            )));
         }
-        if !check_simple_matrices(polynomial) {
-            return false;
+        let matrix_families = fuzzed_matrices
+            .families
+            .iter()
+            .map(|name| {
+                std::iter::once("zero_pattern")
+                    .chain(matrix_family::FAMILY_NAMES)
+                    .find(|known| known == name)
+                    .ok_or_else(|| {
+                        Error::InvalidState(format!(
+                            "the stored sample names unknown matrix family \"{}\"",
+                            name
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<&'static str>>>()?;
+        info!(
+            "Using the {} stored matrices of the run",
+            fuzzed_matrices.matrices.len()
+        );
+        let matrices = PowerTable::from_matrices(&fuzzed_matrices.matrices, size, powers);
+        Ok(Self::with_matrices(config, matrices, matrix_families))
+    }
+
+    fn with_matrices(
+        config: &VerifierConfig,
+        matrices: PowerTable,
+        matrix_families: Vec<&'static str>,
+    ) -> Self {
+        PolynomialVerifier {
+            matrices,
+            matrix_families,
+            exact_arithmetic: config.exact_arithmetic,
+            tolerance: config.tolerance,
+            pool: Arc::new(worker_pool(config.workers)),
+            matrix_search: config.matrix_search,
+            matrix_search_restarts: config.matrix_search_restarts,
+            search_seed: config.seed,
+            verifications: Arc::new(AtomicU64::new(0)),
         }
+    }
 
-        let square_matrix_size = usize::pow(polynomial.get_size(), 2);
-        for j in 0..square_matrix_size {
-            for k in 1..self.matrices.len() {
-                if !&polynomial.is_polynomial_nonnegative_from_matrix(&self.matrices[k]) {
-                    trace!("{}", &self.matrices[k]);
-                    return false;
-                }
-                for entries_to_zero in (0..square_matrix_size).combinations(j) {
-                    let mut matrix = self.matrices[k].clone();
-                    for entry in &entries_to_zero {
-                        let row = entry % polynomial.get_size();
-                        let column = entry / polynomial.get_size();
-                        matrix[(row, column)] = 0.0;
-                    }
-                    if !&polynomial.is_polynomial_nonnegative_from_matrix(&matrix) {
-                        trace!("{}", &matrix);
-                        return false;
-                    }
-                }
-            }
+    // The fuzzed matrices, read back from the power table.
+    pub fn fuzzed_matrices(&self) -> FuzzedMatrices {
+        FuzzedMatrices {
+            matrix_size: self.matrices.matrix_size(),
+            families: self
+                .matrix_families
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            matrices: (0..self.matrices.len())
+                .map(|index| self.matrices.matrix(index))
+                .collect(),
         }
-        true
     }
-     */
+
+    pub fn tolerance(&self) -> &Tolerance {
+        &self.tolerance
+    }
+
+    // The pool shared by every job of the run. Parallel checks started from inside it are
+    // scheduled on the same workers.
+    pub fn pool(&self) -> &ThreadPool {
+        &self.pool
+    }
+
+    pub fn workers(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    // Polynomials verified since the verifier was built.
+    pub fn verifications(&self) -> u64 {
+        self.verifications.load(Ordering::Relaxed)
+    }
 
     pub fn test_polynomial(&self, polynomial: &Polynomial) -> bool {
+        self.verify(polynomial).preserves
+    }
+
+    // Same as `test_polynomial` but splits the fuzzed matrices across the pool.
+    pub fn test_polynomial_parallel(&self, polynomial: &Polynomial) -> bool {
+        self.verify_parallel(polynomial).preserves
+    }
+
+    // Tests every polynomial against the shared matrix powers, in parallel over the polynomials
+    // when there are enough of them to keep the workers busy and over the matrices otherwise.
+    pub fn test_polynomials(&self, polynomials: &[Polynomial]) -> Vec<bool> {
+        if polynomials.len() < self.workers() {
+            return polynomials
+                .iter()
+                .map(|polynomial| self.test_polynomial_parallel(polynomial))
+                .collect();
+        }
+        self.pool.install(|| {
+            polynomials
+                .par_iter()
+                .map(|polynomial| self.test_polynomial(polynomial))
+                .collect()
+        })
+    }
+
+    // Runs the same checks as `test_polynomial`, reporting which one decided the result and the
+    // offending matrix when the polynomial is rejected.
+    pub fn verify(&self, polynomial: &Polynomial) -> Verdict {
+        self.verify_with(polynomial, |polynomial| {
+            (0..self.matrices.blocks()).find_map(|block| self.check_block(polynomial, block))
+        })
+    }
+
+    // Same verdict as `verify`, with the fuzzed matrices split across the pool. Matrices after a
+    // failing one are skipped, and the first failure in sample order is reported so the
+    // counterexample does not depend on timing.
+    pub fn verify_parallel(&self, polynomial: &Polynomial) -> Verdict {
+        self.verify_with(polynomial, |polynomial| {
+            self.pool.install(|| {
+                (0..self.matrices.blocks())
+                    .into_par_iter()
+                    .find_map_first(|block| self.check_block(polynomial, block))
+            })
+        })
+    }
+
+    fn verify_with<F>(&self, polynomial: &Polynomial, fuzzed_failure: F) -> Verdict
+    where
+        F: FnOnce(&Polynomial) -> Option<Counterexample>,
+    {
+        self.verifications.fetch_add(1, Ordering::Relaxed);
         if polynomial.is_polynomial_nonnegative() {
-            return true;
+            return Verdict::preserved(Check::NonnegativeCoefficients);
+        }
+        if polynomial.are_first_last_negative(&self.tolerance) {
+            return Verdict::rejected(Check::FirstLastNegative, None);
+        }
+        for matrix in simple_matrices(polynomial.get_size()) {
+            if !polynomial.is_polynomial_nonnegative_from_matrix(&matrix, &self.tolerance) {
+                let counterexample = Counterexample::new(polynomial, matrix);
+                if let Some(counterexample) = self.confirm(polynomial, counterexample) {
+                    return Verdict::rejected(Check::SimpleMatrices, Some(counterexample));
+                }
+            }
+        }
+
+        if let Some(counterexample) = fuzzed_failure(polynomial) {
+            trace!("{}", counterexample);
+            return Verdict::rejected(Check::FuzzedMatrices, Some(counterexample));
+        }
+        if !self.matrix_search {
+            return Verdict::preserved(Check::FuzzedMatrices);
+        }
+        if let Some(counterexample) = self.find_violating_matrix(polynomial) {
+            trace!("{}", counterexample);
+            return Verdict::rejected(Check::MatrixSearch, Some(counterexample));
+        }
+        Verdict::preserved(Check::MatrixSearch)
+    }
+
+    // Searches for a nonnegative matrix that makes p(A) negative with `matrix_search_restarts`
+    // restarts of a local search, see `matrix_search`. Returns the best matrix found when p(A) has
+    // an entry below the tolerance.
+    pub fn find_violating_matrix(&self, polynomial: &Polynomial) -> Option<Counterexample> {
+        let mut rng = StdRng::seed_from_u64(self.search_seed);
+        let found = matrix_search::search(polynomial, self.matrix_search_restarts, &mut rng)?;
+        if found.score >= 0.0 {
+            return None;
+        }
+        debug!(
+            "Local search found score {} for {}",
+            found.score, polynomial
+        );
+        let counterexample = Counterexample::new(polynomial, found.matrix);
+        if self.tolerance.is_matrix_nonnegative(&counterexample.result) {
+            return None;
+        }
+        self.confirm(polynomial, counterexample)
+    }
+
+    // Counterexamples for up to `limit` matrices of the sample, at most one from each block, in
+    // sample order.
+    pub fn violating_matrices(&self, polynomial: &Polynomial, limit: usize) -> Vec<Counterexample> {
+        let mut counterexamples: Vec<Counterexample> = self.pool.install(|| {
+            (0..self.matrices.blocks())
+                .into_par_iter()
+                .filter_map(|block| self.check_block(polynomial, block))
+                .collect()
+        });
+        counterexamples.truncate(limit);
+        counterexamples
+    }
+
+    // The counterexample for the first matrix in `block` that p(A) is negative for.
+    fn check_block(&self, polynomial: &Polynomial, block: usize) -> Option<Counterexample> {
+        let mask = self
+            .matrices
+            .negative_matrices(polynomial, &self.tolerance, block);
+        if mask == 0 {
+            return None;
         }
-        if polynomial.are_first_last_negative() {
-            return false;
+        self.matrices
+            .block_range(block)
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .find_map(|(_, index)| {
+                let matrix_powers = self.matrices.matrix_powers(index);
+                let mut counterexample = Counterexample::from_powers(polynomial, &matrix_powers);
+                counterexample.family = Some(self.matrix_families[index].to_string());
+                self.confirm(polynomial, counterexample)
+            })
+    }
+
+    // The largest t such that `polynomial` with t taken off every coefficient in `combination`
+    // passes the simple matrices and every matrix of the sample. Every entry of p(A) - t d(A) is
+    // linear in t, so this is a ratio test: the smallest value / slope over the entries with a
+    // positive slope. The result is pulled in by `REDUCTION_MARGIN` so rounding does not make the
+    // binding entry fail the check. The allowance of a relative tolerance is taken at
+    // `polynomial`. Returns None when no shift passes or nothing bounds it.
+    pub fn max_reduction(&self, polynomial: &Polynomial, combination: &[usize]) -> Option<f64> {
+        let length = polynomial.len();
+        let size = polynomial.get_size();
+        let mut direction = Polynomial::from_element(length, size, 0.0);
+        for i in combination {
+            direction[*i] = 1.0;
         }
-        if !check_simple_matrices(polynomial) {
-            return false;
+        let mut lower = f64::NEG_INFINITY;
+        let mut upper = f64::INFINITY;
+        let mut bound = |slope: f64, room: f64| {
+            // slope t <= room
+            if slope > 0.0 {
+                upper = upper.min(room / slope);
+            } else if slope < 0.0 {
+                lower = lower.max(room / slope);
+            } else if room < 0.0 {
+                lower = f64::INFINITY;
+            }
+        };
+
+        // The first and last terms have to stay nonnegative.
+        for i in combination {
+            if *i < size || *i + size >= length {
+                bound(1.0, polynomial[*i]);
+            }
         }
 
-        for k in 1..self.matrices.len() {
-            if !&polynomial.is_polynomial_nonnegative_from_matrix_with_powers(&self.matrices[k]) {
-                trace!("{}", &self.matrices[k][0]);
-                return false;
+        // Every entry of p(A) - t d(A) has to stay above the allowance.
+        for matrix in simple_matrices(size) {
+            let values = polynomial.evaluate_matrix(&matrix);
+            let slopes = direction.evaluate_matrix(&matrix);
+            let allowance = self.tolerance.allowance(&values);
+            for (value, slope) in values.iter().zip(slopes.iter()) {
+                bound(*slope, value + allowance);
             }
         }
-        true
+
+        let weights: Vec<f64> = (0..length).map(|k| polynomial[length - 1 - k]).collect();
+        let slope_weights: Vec<f64> = (0..length).map(|k| direction[length - 1 - k]).collect();
+        for block in 0..self.matrices.blocks() {
+            let (block_lower, block_upper) =
+                self.matrices
+                    .shift_bounds(&weights, &slope_weights, &self.tolerance, block)?;
+            lower = lower.max(block_lower);
+            upper = upper.min(block_upper);
+        }
+
+        if !upper.is_finite() {
+            debug!("Nothing bounds the reduction of {}", polynomial);
+            return None;
+        }
+        let reduction = upper - REDUCTION_MARGIN * upper.abs().max(1.0);
+        (lower <= reduction).then_some(reduction)
     }
-}
 
-fn check_simple_matrices(polynomial: &Polynomial) -> bool {
-    let mut identity = DMatrix::<f64>::identity(polynomial.get_size(), polynomial.get_size());
-    if !&polynomial.is_polynomial_nonnegative_from_matrix(&identity) {
-        return false;
+    // With exact arithmetic enabled a float failure is recomputed in rationals, and dropped as
+    // rounding noise if p(A) turns out to be nonnegative.
+    fn confirm(
+        &self,
+        polynomial: &Polynomial,
+        mut counterexample: Counterexample,
+    ) -> Option<Counterexample> {
+        if self.exact_arithmetic {
+            let certificate = exact::certify(polynomial, &counterexample.matrix);
+            if !certificate.is_negative {
+                debug!(
+                    "Ignoring rounding error of {} for {}",
+                    counterexample.min_entry, polynomial
+                );
+                return None;
+            }
+            counterexample.certificate = Some(certificate);
+        }
+        Some(counterexample)
     }
-    // Go through some permutation matrices
-    for i in 1..polynomial.get_size() {
+}
+
+// The identity followed by some permutation matrices.
+pub fn simple_matrices(matrix_size: usize) -> Vec<DMatrix<f64>> {
+    let mut identity = DMatrix::<f64>::identity(matrix_size, matrix_size);
+    let mut matrices = vec![identity.clone()];
+    for i in 1..matrix_size {
         identity.swap_rows(0, i);
-        if !&polynomial.is_polynomial_nonnegative_from_matrix(&identity) {
-            return false;
+        matrices.push(identity.clone());
+    }
+    matrices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verifier_config(families: &[&str], matrix_search: bool) -> VerifierConfig {
+        let names: Vec<String> = families.iter().map(|name| name.to_string()).collect();
+        VerifierConfig {
+            matrices_to_fuzz: 256,
+            families: matrix_family::from_names(&names).unwrap(),
+            zero_pattern_samples: 0,
+            exact_arithmetic: false,
+            tolerance: Tolerance::default(),
+            seed: 7,
+            workers: 2,
+            matrix_search,
+            matrix_search_restarts: 20,
+        }
+    }
+
+    // A verifier that only tests `matrices`.
+    fn verifier_with(matrices: Vec<DMatrix<f64>>, matrix_search: bool) -> PolynomialVerifier {
+        let fuzzed_matrices = FuzzedMatrices {
+            matrix_size: 2,
+            families: vec![String::from("random"); matrices.len()],
+            matrices,
+        };
+        PolynomialVerifier::from_fuzzed_matrices(
+            &verifier_config(&["random"], matrix_search),
+            &fuzzed_matrices,
+            5,
+        )
+        .unwrap()
+    }
+
+    // Passes the identity and the swap, but x^4 - 2x^2 + 1 decreases on (0, 1), so an upper
+    // triangular A with diagonal 0.5 and 0 makes the off-diagonal entry of p(A) negative.
+    fn decreasing_polynomial() -> Polynomial {
+        Polynomial::from_vec(vec![1.0, 0.1, -2.0, 0.1, 1.0], 2)
+    }
+
+    fn triangular_matrix() -> DMatrix<f64> {
+        DMatrix::from_row_slice(2, 2, &[0.5, 1.0, 0.0, 0.0])
+    }
+
+    #[test]
+    fn nonnegative_coefficients_preserve() {
+        let verifier = verifier_with(vec![triangular_matrix()], false);
+        let verdict = verifier.verify(&Polynomial::from_vec(vec![1.0, 0.0, 2.0], 2));
+        assert!(verdict.preserves);
+        assert_eq!(verdict.check, Check::NonnegativeCoefficients);
+        assert!(verdict.counterexample.is_none());
+    }
+
+    #[test]
+    fn negative_last_term_is_rejected() {
+        let verifier = verifier_with(vec![triangular_matrix()], false);
+        let verdict = verifier.verify(&Polynomial::from_vec(vec![1.0, 1.0, 1.0, 1.0, -1.0], 2));
+        assert!(!verdict.preserves);
+        assert_eq!(verdict.check, Check::FirstLastNegative);
+        assert!(verdict.counterexample.is_none());
+    }
+
+    #[test]
+    fn identity_is_a_counterexample() {
+        let verifier = verifier_with(vec![triangular_matrix()], false);
+        let polynomial = Polynomial::from_vec(vec![1.0, 1.0, -10.0, 1.0, 1.0], 2);
+        let verdict = verifier.verify(&polynomial);
+        assert!(!verdict.preserves);
+        assert_eq!(verdict.check, Check::SimpleMatrices);
+        let counterexample = verdict.counterexample.unwrap();
+        assert_eq!(counterexample.matrix, DMatrix::identity(2, 2));
+        assert_eq!(counterexample.result, DMatrix::identity(2, 2) * -6.0);
+        assert_eq!(counterexample.min_entry, -6.0);
+        assert_eq!(counterexample.min_index, (0, 0));
+        assert!(counterexample.family.is_none());
+    }
+
+    #[test]
+    fn fuzzed_matrix_is_a_counterexample() {
+        let verifier = verifier_with(vec![DMatrix::identity(2, 2), triangular_matrix()], false);
+        let polynomial = decreasing_polynomial();
+        for verdict in [
+            verifier.verify(&polynomial),
+            verifier.verify_parallel(&polynomial),
+        ] {
+            assert!(!verdict.preserves);
+            assert_eq!(verdict.check, Check::FuzzedMatrices);
+            let counterexample = verdict.counterexample.unwrap();
+            assert_eq!(counterexample.matrix, triangular_matrix());
+            assert_eq!(
+                counterexample.result,
+                polynomial.evaluate_matrix(&triangular_matrix())
+            );
+            // p(0.5) = 0.625 and p(0) = 1, so the entry above the diagonal is (0.625 - 1) / 0.5.
+            assert!((counterexample.min_entry + 0.75).abs() < 1e-12);
+            assert_eq!(counterexample.min_index, (0, 1));
+            assert_eq!(counterexample.family.as_deref(), Some("random"));
         }
     }
-    true
+
+    #[test]
+    fn matrix_search_finds_a_counterexample() {
+        let polynomial = decreasing_polynomial();
+        let verdict = verifier_with(vec![DMatrix::identity(2, 2)], false).verify(&polynomial);
+        assert!(verdict.preserves);
+        assert_eq!(verdict.check, Check::FuzzedMatrices);
+
+        let verdict = verifier_with(vec![DMatrix::identity(2, 2)], true).verify(&polynomial);
+        assert!(!verdict.preserves);
+        assert_eq!(verdict.check, Check::MatrixSearch);
+        let counterexample = verdict.counterexample.unwrap();
+        assert!(counterexample.min_entry < 0.0);
+        assert_eq!(counterexample.min_entry, counterexample.result.min());
+        let (row, column) = counterexample.min_index;
+        assert_eq!(
+            counterexample.result[(row, column)],
+            counterexample.min_entry
+        );
+        assert!(counterexample.matrix.iter().all(|entry| *entry >= 0.0));
+    }
+
+    #[test]
+    fn max_reduction_is_the_largest_passing_shift() {
+        let verifier = verifier_with(vec![DMatrix::identity(2, 2), triangular_matrix()], false);
+        let polynomial = Polynomial::from_vec(vec![1.0, 0.1, 1.0, 0.1, 1.0], 2);
+        let reduce = |reduction: f64| {
+            let mut reduced = polynomial.clone();
+            reduced[2] -= reduction;
+            reduced
+        };
+        let reduction = verifier.max_reduction(&polynomial, &[2]).unwrap();
+        assert!(reduction > 0.0);
+        assert!(verifier.verify(&reduce(reduction)).preserves);
+        assert!(!verifier.verify(&reduce(reduction * 1.001)).preserves);
+
+        // The first and last terms bound the shift by their own coefficient.
+        let reduction = verifier.max_reduction(&polynomial, &[0, 4]).unwrap();
+        assert!(reduction <= 1.0);
+        assert!((reduction - 1.0).abs() < 1e-6);
+        let mut reduced = polynomial.clone();
+        reduced[0] -= reduction;
+        reduced[4] -= reduction;
+        assert!(verifier.verify(&reduced).preserves);
+    }
+
+    #[test]
+    fn same_seed_draws_the_same_matrices() {
+        let config = verifier_config(&["circulant", "random"], false);
+        let draw = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            PolynomialVerifier::new(&config, 3, 4, &mut rng).fuzzed_matrices()
+        };
+        let first = draw(11);
+        assert_eq!(first.matrices.len(), 256);
+        assert_eq!(first.matrices, draw(11).matrices);
+        assert_eq!(first.families, draw(11).families);
+        assert_ne!(first.matrices, draw(12).matrices);
+    }
+
+    #[test]
+    fn stored_matrices_give_the_same_verdicts() {
+        let config = verifier_config(&["upper_triangular"], false);
+        let mut rng = StdRng::seed_from_u64(3);
+        let verifier = PolynomialVerifier::new(&config, 2, 5, &mut rng);
+        let stored =
+            PolynomialVerifier::from_fuzzed_matrices(&config, &verifier.fuzzed_matrices(), 5)
+                .unwrap();
+        let polynomials = [
+            decreasing_polynomial(),
+            Polynomial::from_vec(vec![1.0, 1.0, -0.1, 1.0, 1.0], 2),
+        ];
+        for polynomial in &polynomials {
+            let verdict = verifier.verify(polynomial);
+            let stored_verdict = stored.verify(polynomial);
+            assert_eq!(verdict.preserves, stored_verdict.preserves);
+            assert_eq!(verdict.check, stored_verdict.check);
+        }
+    }
+
+    #[test]
+    fn exact_arithmetic_certifies_and_clears() {
+        let mut config = verifier_config(&["random"], false);
+        config.exact_arithmetic = true;
+        let fuzzed_matrices = FuzzedMatrices {
+            matrix_size: 2,
+            families: vec![String::from("random")],
+            matrices: vec![triangular_matrix()],
+        };
+        let verifier =
+            PolynomialVerifier::from_fuzzed_matrices(&config, &fuzzed_matrices, 5).unwrap();
+        let verdict = verifier.verify(&decreasing_polynomial());
+        assert_eq!(verdict.check, Check::FuzzedMatrices);
+        let certificate = verdict.counterexample.unwrap().certificate.unwrap();
+        assert!(certificate.is_negative);
+        assert_eq!(certificate.min_index, (0, 1));
+
+        // (x - a)^2 (x + b) with float coefficients is slightly negative at A = a in floating
+        // point, but not in exact arithmetic.
+        let (a, b) = (0.56, 2.14);
+        let polynomial =
+            Polynomial::from_vec(vec![1.0, b - 2.0 * a, a * a - 2.0 * a * b, a * a * b], 1);
+        let fuzzed_matrices = FuzzedMatrices {
+            matrix_size: 1,
+            families: vec![String::from("random")],
+            matrices: vec![DMatrix::from_element(1, 1, a)],
+        };
+        config.exact_arithmetic = false;
+        let float_verdict = PolynomialVerifier::from_fuzzed_matrices(&config, &fuzzed_matrices, 4)
+            .unwrap()
+            .verify(&polynomial);
+        assert!(!float_verdict.preserves);
+        assert_eq!(float_verdict.check, Check::FuzzedMatrices);
+        config.exact_arithmetic = true;
+        let exact_verdict = PolynomialVerifier::from_fuzzed_matrices(&config, &fuzzed_matrices, 4)
+            .unwrap()
+            .verify(&polynomial);
+        assert!(exact_verdict.preserves);
+    }
 }
@@ -0,0 +1,383 @@
+use crate::matrix_generator;
+use crate::polynomial::Polynomial;
+use crate::tolerance::Tolerance;
+use nalgebra::{DMatrix, SMatrix};
+use std::ops::Range;
+
+// Matrices evaluated together by `negative_matrices`, one bit of the returned mask each.
+pub const BLOCK_SIZE: usize = 64;
+
+// The powers [I, A, A^2, ...] of every fuzzed matrix in one contiguous buffer. The buffer is laid
+// out power by power, then entry by entry, with the matrices innermost, so one entry of one power
+// is a contiguous run over all matrices. p(A) for every matrix is then the product of the
+// coefficient vector with the table, computed a block of matrices at a time without allocating.
+#[derive(Debug, Clone)]
+pub struct PowerTable {
+    matrix_size: usize,
+    powers: usize,
+    number_of_matrices: usize,
+    entries: Vec<f64>,
+}
+
+impl PowerTable {
+    // A table with room for `number_of_matrices` matrices, filled in with `set_matrix`.
+    pub fn new(matrix_size: usize, powers: usize, number_of_matrices: usize) -> Self {
+        PowerTable {
+            matrix_size,
+            powers,
+            number_of_matrices,
+            entries: vec![0.0; powers * matrix_size * matrix_size * number_of_matrices],
+        }
+    }
+
+    pub fn from_matrices(matrices: &[DMatrix<f64>], matrix_size: usize, powers: usize) -> Self {
+        let mut table = PowerTable::new(matrix_size, powers, matrices.len());
+        for (index, matrix) in matrices.iter().enumerate() {
+            table.set_matrix(index, matrix);
+        }
+        table
+    }
+
+    // Stores the powers of `matrix` at `index`, computed with stack allocated matrices for 2x2 to
+    // 4x4.
+    pub fn set_matrix(&mut self, index: usize, matrix: &DMatrix<f64>) {
+        match self.matrix_size {
+            2 => self.set_matrix_fixed::<2>(index, matrix),
+            3 => self.set_matrix_fixed::<3>(index, matrix),
+            4 => self.set_matrix_fixed::<4>(index, matrix),
+            _ => self.set_matrix_dynamic(index, matrix),
+        }
+    }
+
+    pub fn set_matrix_dynamic(&mut self, index: usize, matrix: &DMatrix<f64>) {
+        let matrix_powers = matrix_generator::generate_matrix_powers(matrix, self.powers);
+        for (power, matrix_power) in matrix_powers.iter().enumerate() {
+            self.set_power(index, power, matrix_power.iter());
+        }
+    }
+
+    fn set_matrix_fixed<const N: usize>(&mut self, index: usize, matrix: &DMatrix<f64>) {
+        let matrix = SMatrix::<f64, N, N>::from_iterator(matrix.iter().copied());
+        let mut working_matrix = SMatrix::<f64, N, N>::identity();
+        for power in 0..self.powers {
+            self.set_power(index, power, working_matrix.iter());
+            working_matrix *= matrix;
+        }
+    }
+
+    // nalgebra iterates in column-major order, matching `matrix_powers` below.
+    fn set_power<'a>(
+        &mut self,
+        index: usize,
+        power: usize,
+        entries: impl Iterator<Item = &'a f64>,
+    ) {
+        let entries_per_matrix = self.matrix_size * self.matrix_size;
+        for (entry, value) in entries.enumerate() {
+            let row = power * entries_per_matrix + entry;
+            self.entries[row * self.number_of_matrices + index] = *value;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.number_of_matrices
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.number_of_matrices == 0
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.number_of_matrices.div_ceil(BLOCK_SIZE)
+    }
+
+    pub fn block_range(&self, block: usize) -> Range<usize> {
+        let start = block * BLOCK_SIZE;
+        start..(start + BLOCK_SIZE).min(self.number_of_matrices)
+    }
+
+    // Copies the powers of one matrix back out of the table.
+    pub fn matrix_powers(&self, index: usize) -> Vec<DMatrix<f64>> {
+        let entries_per_matrix = self.matrix_size * self.matrix_size;
+        (0..self.powers)
+            .map(|power| {
+                DMatrix::from_iterator(
+                    self.matrix_size,
+                    self.matrix_size,
+                    (0..entries_per_matrix).map(|entry| {
+                        let row = power * entries_per_matrix + entry;
+                        self.entries[row * self.number_of_matrices + index]
+                    }),
+                )
+            })
+            .collect()
+    }
+
+    // The matrix stored at `index`, its first power. Needs a table of at least two powers.
+    pub fn matrix(&self, index: usize) -> DMatrix<f64> {
+        assert!(self.powers >= 2, "the table does not hold the first power");
+        let entries_per_matrix = self.matrix_size * self.matrix_size;
+        DMatrix::from_iterator(
+            self.matrix_size,
+            self.matrix_size,
+            (0..entries_per_matrix).map(|entry| {
+                let row = entries_per_matrix + entry;
+                self.entries[row * self.number_of_matrices + index]
+            }),
+        )
+    }
+
+    pub fn matrix_size(&self) -> usize {
+        self.matrix_size
+    }
+
+    pub fn entries_per_matrix(&self) -> usize {
+        self.matrix_size * self.matrix_size
+    }
+
+    // The range of t for which p(A) - t d(A) stays above the tolerance for every matrix in
+    // `block`, where p and d have `weights` and `slope_weights` in power order. Each entry is a
+    // constraint that is linear in t. The allowance of a relative tolerance is taken at t = 0.
+    // Returns None when no t works.
+    pub fn shift_bounds(
+        &self,
+        weights: &[f64],
+        slope_weights: &[f64],
+        tolerance: &Tolerance,
+        block: usize,
+    ) -> Option<(f64, f64)> {
+        let range = self.block_range(block);
+        let matrices = range.len();
+        let entries_per_matrix = self.entries_per_matrix();
+        let terms = weights.len().min(slope_weights.len()).min(self.powers);
+
+        let mut allowance = [tolerance.absolute; BLOCK_SIZE];
+        if tolerance.relative != 0.0 {
+            let mut amax = [0.0f64; BLOCK_SIZE];
+            for entry in 0..entries_per_matrix {
+                let mut values = [0.0f64; BLOCK_SIZE];
+                let values = &mut values[..matrices];
+                for (power, weight) in weights.iter().enumerate().take(terms) {
+                    let row = (power * entries_per_matrix + entry) * self.number_of_matrices;
+                    let table = &self.entries[row + range.start..row + range.end];
+                    for (value, power_entry) in values.iter_mut().zip(table) {
+                        *value += weight * power_entry;
+                    }
+                }
+                for (amax, value) in amax.iter_mut().zip(values.iter()) {
+                    *amax = amax.max(value.abs());
+                }
+            }
+            for (allowance, amax) in allowance.iter_mut().zip(&amax) {
+                *allowance += tolerance.relative * amax;
+            }
+        }
+
+        let mut lower = f64::NEG_INFINITY;
+        let mut upper = f64::INFINITY;
+        for entry in 0..entries_per_matrix {
+            let mut values = [0.0f64; BLOCK_SIZE];
+            let mut slopes = [0.0f64; BLOCK_SIZE];
+            let values = &mut values[..matrices];
+            let slopes = &mut slopes[..matrices];
+            for power in 0..terms {
+                let row = (power * entries_per_matrix + entry) * self.number_of_matrices;
+                let table = &self.entries[row + range.start..row + range.end];
+                let (weight, slope_weight) = (weights[power], slope_weights[power]);
+                for ((value, slope), power_entry) in
+                    values.iter_mut().zip(slopes.iter_mut()).zip(table)
+                {
+                    *value += weight * power_entry;
+                    *slope += slope_weight * power_entry;
+                }
+            }
+            for ((value, slope), allowance) in values.iter().zip(slopes.iter()).zip(&allowance) {
+                // slope t <= value + allowance
+                let room = value + allowance;
+                if *slope > 0.0 {
+                    upper = upper.min(room / slope);
+                } else if *slope < 0.0 {
+                    lower = lower.max(room / slope);
+                } else if room < 0.0 {
+                    return None;
+                }
+            }
+        }
+        Some((lower, upper))
+    }
+
+    // Evaluates p(A) for the matrices in `block` and sets bit i of the result when p(A) of the
+    // i-th matrix in the block has an entry below the tolerance. Terms past the stored powers are
+    // ignored, like `Polynomial::evaluate_matrix_with_powers`. 2x2 matrices are evaluated by
+    // `negative_matrices_fixed`, which measures about a fifth faster in benches/fixed_size.rs.
+    // From 3x3 on the N^3 products of Horner's method cost more than streaming the stored powers,
+    // so larger matrices are not specialized.
+    pub fn negative_matrices(
+        &self,
+        polynomial: &Polynomial,
+        tolerance: &Tolerance,
+        block: usize,
+    ) -> u64 {
+        match self.matrix_size {
+            2 => self.negative_matrices_fixed::<2>(polynomial, tolerance, block),
+            _ => self.negative_matrices_dynamic(polynomial, tolerance, block),
+        }
+    }
+
+    // Reads only the first power of each matrix from the table and computes p(A) by Horner's
+    // method in stack allocated matrices, instead of streaming every stored power.
+    pub fn negative_matrices_fixed<const N: usize>(
+        &self,
+        polynomial: &Polynomial,
+        tolerance: &Tolerance,
+        block: usize,
+    ) -> u64 {
+        assert_eq!(self.matrix_size, N, "the table holds other matrices");
+        if self.powers < 2 {
+            return self.negative_matrices_dynamic(polynomial, tolerance, block);
+        }
+        let terms = polynomial.len().min(self.powers);
+        let coefficients = &polynomial.coefficients()[polynomial.len() - terms..];
+
+        let mut mask = 0;
+        for (i, index) in self.block_range(block).enumerate() {
+            // Column-major like nalgebra, entry `column * N + row`.
+            let matrix = SMatrix::<f64, N, N>::from_fn(|row, column| {
+                let entry = column * N + row;
+                self.entries[(N * N + entry) * self.number_of_matrices + index]
+            });
+            let mut result = SMatrix::<f64, N, N>::zeros();
+            for coefficient in coefficients {
+                result *= matrix;
+                for diagonal in 0..N {
+                    result[(diagonal, diagonal)] += coefficient;
+                }
+            }
+            let allowance = if tolerance.relative == 0.0 {
+                tolerance.absolute
+            } else {
+                tolerance.absolute + tolerance.relative * result.amax()
+            };
+            if result.min() < -allowance {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+
+    pub fn negative_matrices_dynamic(
+        &self,
+        polynomial: &Polynomial,
+        tolerance: &Tolerance,
+        block: usize,
+    ) -> u64 {
+        let entries_per_matrix = self.entries_per_matrix();
+        let range = self.block_range(block);
+        let matrices = range.len();
+        let terms = polynomial.len().min(self.powers);
+
+        let mut min = [f64::INFINITY; BLOCK_SIZE];
+        let mut amax = [0.0f64; BLOCK_SIZE];
+        for entry in 0..entries_per_matrix {
+            let mut values = [0.0f64; BLOCK_SIZE];
+            let values = &mut values[..matrices];
+            for power in 0..terms {
+                let coefficient = polynomial[polynomial.len() - 1 - power];
+                let row = (power * entries_per_matrix + entry) * self.number_of_matrices;
+                let table = &self.entries[row + range.start..row + range.end];
+                for (value, power_entry) in values.iter_mut().zip(table) {
+                    *value += coefficient * power_entry;
+                }
+            }
+            for (i, value) in values.iter().enumerate() {
+                if *value < min[i] {
+                    min[i] = *value;
+                }
+                amax[i] = amax[i].max(value.abs());
+            }
+        }
+
+        let mut mask = 0;
+        for i in 0..matrices {
+            let allowance = if tolerance.relative == 0.0 {
+                tolerance.absolute
+            } else {
+                tolerance.absolute + tolerance.relative * amax[i]
+            };
+            if min[i] < -allowance {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix_family;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn random_matrices(matrix_size: usize, number_of_matrices: usize) -> Vec<DMatrix<f64>> {
+        let family = matrix_family::from_name("random").unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        (0..number_of_matrices)
+            .map(|_| family.generate(matrix_size, &mut rng).scale(0.01))
+            .collect()
+    }
+
+    #[test]
+    fn fixed_and_dynamic_paths_agree() {
+        let tolerance = Tolerance::default();
+        for matrix_size in 1..=5 {
+            let polynomial = Polynomial::from_vec(vec![1.0, 0.5, -0.2, 0.5, 1.0], matrix_size);
+            let matrices = random_matrices(matrix_size, 100);
+            let table = PowerTable::from_matrices(&matrices, matrix_size, 5);
+            let mut dynamic = PowerTable::new(matrix_size, 5, matrices.len());
+            for (index, matrix) in matrices.iter().enumerate() {
+                dynamic.set_matrix_dynamic(index, matrix);
+                assert_eq!(table.matrix(index), *matrix);
+            }
+            for index in 0..matrices.len() {
+                for (fixed, general) in table
+                    .matrix_powers(index)
+                    .iter()
+                    .zip(dynamic.matrix_powers(index))
+                {
+                    assert!((fixed - &general).amax() <= 1e-12 * general.amax());
+                }
+            }
+            for block in 0..table.blocks() {
+                let dynamic = table.negative_matrices_dynamic(&polynomial, &tolerance, block);
+                assert_eq!(
+                    table.negative_matrices(&polynomial, &tolerance, block),
+                    dynamic
+                );
+                let fixed = match matrix_size {
+                    2 => table.negative_matrices_fixed::<2>(&polynomial, &tolerance, block),
+                    3 => table.negative_matrices_fixed::<3>(&polynomial, &tolerance, block),
+                    4 => table.negative_matrices_fixed::<4>(&polynomial, &tolerance, block),
+                    _ => dynamic,
+                };
+                assert_eq!(fixed, dynamic);
+            }
+        }
+    }
+
+    #[test]
+    fn masks_the_negative_matrices() {
+        // p(x) = x^2 - x + 0.1 is negative at 0.5 only.
+        let polynomial = Polynomial::from_vec(vec![1.0, -1.0, 0.1], 2);
+        let matrices: Vec<DMatrix<f64>> = [0.0, 0.5, 2.0]
+            .iter()
+            .map(|value| DMatrix::identity(2, 2).scale(*value))
+            .collect();
+        let table = PowerTable::from_matrices(&matrices, 2, 3);
+        assert_eq!(table.blocks(), 1);
+        assert_eq!(
+            table.negative_matrices(&polynomial, &Tolerance::default(), 0),
+            0b010
+        );
+    }
+}
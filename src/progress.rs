@@ -0,0 +1,329 @@
+// Progress of a mutate run through the combinations of each generation, shown as a line on the
+// terminal and optionally appended to a file as JSON lines for dashboards.
+use crate::current_state::CurrentState;
+use crate::error::{Error, Result};
+use crate::output;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Instant, SystemTime};
+
+// The progress line drawn on standard error, None when the cursor is at the start of an empty
+// line. Log lines are written through `suspend` so they do not run into it.
+static LINE: Mutex<Option<String>> = Mutex::new(None);
+
+// Runs `write`, which prints whole lines to standard error, with the progress line cleared, and
+// draws the line again below them.
+pub fn suspend<T>(write: impl FnOnce() -> T) -> T {
+    let line = LINE.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut stderr = io::stderr().lock();
+    if line.is_some() {
+        let _ = write!(stderr, "\r\x1b[K");
+    }
+    let result = write();
+    if let Some(line) = &*line {
+        let _ = write!(stderr, "{}", line);
+        let _ = stderr.flush();
+    }
+    result
+}
+
+// Settings of the progress reports, loaded from the `[config.progress]` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressConfig {
+    // Redraw a progress line on standard error when it is a terminal.
+    pub display: bool,
+    // Append a JSON object per report to this file.
+    pub json_lines: Option<PathBuf>,
+    // Seconds between reports, the end of every generation is always reported.
+    pub interval_seconds: f64,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        ProgressConfig {
+            display: true,
+            json_lines: None,
+            interval_seconds: 1.0,
+        }
+    }
+}
+
+// One progress report, also the format of the JSON lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressReport {
+    // Seconds since the Unix epoch.
+    pub time: u64,
+    pub generation: usize,
+    pub generations: usize,
+    // Combinations of the generation minimized so far and in total, counting the ones finished
+    // before the run was resumed.
+    pub combinations_done: usize,
+    pub combinations_total: usize,
+    // Polynomials minimized for every combination.
+    pub polynomials_per_combination: usize,
+    // Interesting polynomials found in the generation so far.
+    pub polynomials_found: usize,
+    pub verifications: u64,
+    pub verifications_per_second: f64,
+    // Seconds spent on the generation since the run was started or resumed.
+    pub elapsed_seconds: f64,
+    // Estimated seconds until the generation is finished, None until a combination finished.
+    pub remaining_seconds: Option<f64>,
+    // Estimated seconds until the last generation is finished, taking every later generation to
+    // last as long as a whole one at the current rate. None until a combination finished.
+    #[serde(default)]
+    pub run_remaining_seconds: Option<f64>,
+}
+
+pub struct Progress {
+    config: ProgressConfig,
+    terminal: bool,
+    json_lines: Option<File>,
+    generation: usize,
+    generations: usize,
+    combinations_total: usize,
+    // Combinations already done when the generation was started or resumed.
+    combinations_before: usize,
+    combinations_done: usize,
+    polynomials_per_combination: usize,
+    polynomials_found: usize,
+    // Verifier count at the start of the generation.
+    verifications_before: u64,
+    verifications: u64,
+    started: Instant,
+    last_report: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(config: &ProgressConfig) -> Result<Self> {
+        let json_lines = match &config.json_lines {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|source| Error::io(path, source))?,
+            ),
+            None => None,
+        };
+        Ok(Progress {
+            config: config.clone(),
+            terminal: config.display && io::stderr().is_terminal(),
+            json_lines,
+            generation: 0,
+            generations: 0,
+            combinations_total: 0,
+            combinations_before: 0,
+            combinations_done: 0,
+            polynomials_per_combination: 0,
+            polynomials_found: 0,
+            verifications_before: 0,
+            verifications: 0,
+            started: Instant::now(),
+            last_report: None,
+        })
+    }
+
+    // Starts counting the current generation of `current_state` out of `generations`, with the
+    // verifier's count of verifications so far.
+    pub fn start_generation(
+        &mut self,
+        current_state: &CurrentState,
+        generations: usize,
+        verifications: u64,
+    ) {
+        let combinations_total = current_state.combinations_total();
+        self.generation = current_state.current_generation;
+        self.generations = generations;
+        self.combinations_total = combinations_total;
+        self.combinations_before =
+            combinations_total.saturating_sub(current_state.combinations_remaining());
+        self.combinations_done = self.combinations_before;
+        self.polynomials_per_combination = current_state.starting_mutated_polynomials.len();
+        self.polynomials_found = current_state.interesting_polynomials.len();
+        self.verifications_before = verifications;
+        self.verifications = verifications;
+        self.started = Instant::now();
+        self.last_report = None;
+    }
+
+    // Counts a finished combination. Reports when the interval has passed.
+    pub fn finish_combination(&mut self, current_state: &CurrentState, verifications: u64) {
+        self.combinations_done += 1;
+        self.polynomials_found = current_state.interesting_polynomials.len();
+        self.verifications = verifications;
+        let due = self
+            .last_report
+            .is_none_or(|last| last.elapsed().as_secs_f64() >= self.config.interval_seconds);
+        if due {
+            self.report(false);
+        }
+    }
+
+    // Reports the generation when it is finished or the run stops, ending the terminal line.
+    pub fn finish_generation(&mut self, current_state: &CurrentState, verifications: u64) {
+        self.polynomials_found = current_state.interesting_polynomials.len();
+        self.verifications = verifications;
+        self.report(true);
+    }
+
+    pub fn current(&self) -> ProgressReport {
+        let elapsed_seconds = self.started.elapsed().as_secs_f64();
+        let verifications = self.verifications - self.verifications_before;
+        let done_since_start = self.combinations_done - self.combinations_before;
+        let seconds_per_combination =
+            (done_since_start > 0).then(|| elapsed_seconds / done_since_start as f64);
+        let remaining_seconds = seconds_per_combination.map(|seconds| {
+            let remaining = self
+                .combinations_total
+                .saturating_sub(self.combinations_done);
+            seconds * remaining as f64
+        });
+        let later_generations = self.generations.saturating_sub(self.generation + 1);
+        let run_remaining_seconds =
+            seconds_per_combination
+                .zip(remaining_seconds)
+                .map(|(seconds, remaining)| {
+                    remaining + seconds * (self.combinations_total * later_generations) as f64
+                });
+        ProgressReport {
+            time: output::unix_time(SystemTime::now()),
+            generation: self.generation,
+            generations: self.generations,
+            combinations_done: self.combinations_done,
+            combinations_total: self.combinations_total,
+            polynomials_per_combination: self.polynomials_per_combination,
+            polynomials_found: self.polynomials_found,
+            verifications,
+            verifications_per_second: if elapsed_seconds > 0.0 {
+                verifications as f64 / elapsed_seconds
+            } else {
+                0.0
+            },
+            elapsed_seconds,
+            remaining_seconds,
+            run_remaining_seconds,
+        }
+    }
+
+    // Reporting is best effort, a full disk or closed terminal does not stop the run.
+    fn report(&mut self, end_of_generation: bool) {
+        self.last_report = Some(Instant::now());
+        let report = self.current();
+        if self.terminal {
+            let mut line = LINE.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[K{}", report);
+            if end_of_generation {
+                let _ = writeln!(stderr);
+                *line = None;
+            } else {
+                *line = Some(report.to_string());
+            }
+            let _ = stderr.flush();
+        }
+        if let Some(file) = &mut self.json_lines {
+            if let Ok(line) = serde_json::to_string(&report) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+}
+
+// Seconds as e.g. "1h 02m 05s".
+fn duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = if self.combinations_total > 0 {
+            100.0 * self.combinations_done as f64 / self.combinations_total as f64
+        } else {
+            100.0
+        };
+        write!(
+            f,
+            "generation {}/{}  combinations {}/{} ({:.1}%)  {} polynomials each  {} found  \
+             {:.0} verifications/s  elapsed {}",
+            self.generation + 1,
+            self.generations,
+            self.combinations_done,
+            self.combinations_total,
+            percent,
+            self.polynomials_per_combination,
+            self.polynomials_found,
+            self.verifications_per_second,
+            duration(self.elapsed_seconds)
+        )?;
+        match (self.remaining_seconds, self.run_remaining_seconds) {
+            (Some(remaining), Some(run_remaining)) if self.generation + 1 < self.generations => {
+                write!(
+                    f,
+                    "  remaining {} (generation {})",
+                    duration(run_remaining),
+                    duration(remaining)
+                )
+            }
+            (Some(remaining), _) => write!(f, "  remaining {}", duration(remaining)),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Generation `generation` of `generations` with 10 of its 40 combinations done, 4 of them
+    // before the run was resumed and 6 in the 12 seconds since.
+    fn progress(generation: usize, generations: usize) -> Progress {
+        let mut progress = Progress::new(&ProgressConfig {
+            display: false,
+            ..ProgressConfig::default()
+        })
+        .unwrap();
+        progress.generation = generation;
+        progress.generations = generations;
+        progress.combinations_total = 40;
+        progress.combinations_before = 4;
+        progress.combinations_done = 10;
+        progress.started = Instant::now().checked_sub(Duration::from_secs(12)).unwrap();
+        progress
+    }
+
+    #[test]
+    fn estimates_the_remaining_generations() {
+        // 2 seconds per combination, 30 left in this generation and 40 in each of the 2 after it.
+        let report = progress(1, 4).current();
+        let remaining = report.remaining_seconds.unwrap();
+        let run_remaining = report.run_remaining_seconds.unwrap();
+        assert!((remaining - 60.0).abs() < 1.0, "{}", remaining);
+        assert!((run_remaining - 220.0).abs() < 4.0, "{}", run_remaining);
+        let line = report.to_string();
+        assert!(line.contains("generation 2/4"), "{}", line);
+        assert!(line.contains("remaining 3m 4"), "{}", line);
+        assert!(line.contains("(generation 1m 00s)"), "{}", line);
+
+        let report = progress(3, 4).current();
+        assert_eq!(report.remaining_seconds, report.run_remaining_seconds);
+        assert!(!report.to_string().contains("(generation"));
+
+        let mut progress = progress(0, 2);
+        progress.combinations_done = progress.combinations_before;
+        let report = progress.current();
+        assert!(report.remaining_seconds.is_none() && report.run_remaining_seconds.is_none());
+        assert!(!report.to_string().contains("remaining"));
+    }
+}
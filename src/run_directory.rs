@@ -0,0 +1,406 @@
+// A named run keeps its files together so runs started in the same directory do not overwrite
+// each other:
+//
+//   runs/<name>/config.toml   the startup file of the run, written when it is started
+//   runs/<name>/state/        checkpoints of the run state
+//   runs/<name>/output/       results files
+//   runs/<name>/log           log of every command run on it
+//   runs/<name>/lock          id of the process working on the run, while one does
+use crate::config::Config;
+use crate::current_state::CurrentState;
+use crate::error::{Error, Result};
+use crate::export::OutputFormat;
+use log::warn;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_RUNS_DIRECTORY: &str = "runs";
+
+#[derive(Debug, Clone)]
+pub struct RunDirectory {
+    name: String,
+    path: PathBuf,
+}
+
+impl RunDirectory {
+    // The run `name` under `runs_directory`, which does not have to exist yet.
+    pub fn new(runs_directory: &Path, name: &str) -> Result<Self> {
+        let valid = !name.is_empty()
+            && name != "."
+            && name != ".."
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+        if !valid {
+            return Err(Error::InvalidConfig(format!(
+                "run names may only use letters, digits, '-', '_' and '.', not \"{}\"",
+                name
+            )));
+        }
+        Ok(RunDirectory {
+            name: String::from(name),
+            path: runs_directory.join(name),
+        })
+    }
+
+    // A run that has been started before.
+    pub fn open(runs_directory: &Path, name: &str) -> Result<Self> {
+        let run = Self::new(runs_directory, name)?;
+        if !run.config_path().exists() {
+            return Err(Error::InvalidConfig(format!(
+                "there is no run \"{}\" in {}",
+                name,
+                runs_directory.display()
+            )));
+        }
+        Ok(run)
+    }
+
+    // Every run under `runs_directory` sorted by name, none when it does not exist.
+    pub fn list(runs_directory: &Path) -> Result<Vec<Self>> {
+        if !runs_directory.exists() {
+            return Ok(Vec::new());
+        }
+        let entries =
+            fs::read_dir(runs_directory).map_err(|source| Error::io(runs_directory, source))?;
+        let mut runs = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|source| Error::io(runs_directory, source))?;
+            if let Some(name) = entry.file_name().to_str() {
+                if let Ok(run) = Self::open(runs_directory, name) {
+                    runs.push(run);
+                }
+            }
+        }
+        runs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(runs)
+    }
+
+    // Creates the directories of a new run, locks it and writes its config. Refuses a run that
+    // already has a state, which has to be resumed instead.
+    pub fn create(&self, config: &Config) -> Result<RunLock> {
+        if self.state_path().exists() {
+            return Err(Error::InvalidConfig(format!(
+                "run \"{}\" has already been started, resume it or pick another name",
+                self.name
+            )));
+        }
+        for directory in [self.state_directory(), self.output_directory()] {
+            fs::create_dir_all(&directory).map_err(|source| Error::io(&directory, source))?;
+        }
+        let lock = self.lock()?;
+        config.save(&self.config_path())?;
+        Ok(lock)
+    }
+
+    // Takes the lock of an existing run, so a second process can not start or resume it until
+    // the lock is dropped. A lock left behind by a process that no longer runs is taken over.
+    pub fn lock(&self) -> Result<RunLock> {
+        let path = self.lock_path();
+        let mut stale = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())
+                        .map_err(|source| Error::io(&path, source))?;
+                    return Ok(RunLock { path });
+                }
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists && !stale => {
+                    let owner = fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|contents| contents.trim().parse::<u32>().ok());
+                    if owner.is_some_and(process_is_running) {
+                        return Err(Error::InvalidConfig(format!(
+                            "run \"{}\" is in use by process {}",
+                            self.name,
+                            owner.unwrap_or_default()
+                        )));
+                    }
+                    warn!("Taking over the stale lock {}", path.display());
+                    match fs::remove_file(&path) {
+                        Ok(()) => {}
+                        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                        Err(error) => return Err(Error::io(&path, error)),
+                    }
+                    stale = true;
+                }
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    return Err(Error::InvalidConfig(format!(
+                        "run \"{}\" is in use by another process",
+                        self.name
+                    )));
+                }
+                Err(error) => return Err(Error::io(&path, error)),
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.path.join("config.toml")
+    }
+
+    pub fn state_directory(&self) -> PathBuf {
+        self.path.join("state")
+    }
+
+    pub fn state_path(&self) -> PathBuf {
+        self.state_directory().join("state.json")
+    }
+
+    pub fn output_directory(&self) -> PathBuf {
+        self.path.join("output")
+    }
+
+    pub fn output_path(&self, format: OutputFormat) -> PathBuf {
+        self.output_directory()
+            .join(format!("output.{}", format.extension()))
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.path.join("log")
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.path.join("lock")
+    }
+
+    // The log file of a created run, opened for appending.
+    pub fn open_log(&self) -> Result<File> {
+        let log_path = self.log_path();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .map_err(|source| Error::io(&log_path, source))
+    }
+
+    // How far the run has got, read from its config and latest checkpoint.
+    pub fn status(&self) -> Result<RunStatus> {
+        let config = Config::load(&self.config_path())?;
+        let state_path = self.state_path();
+        if !state_path.exists() {
+            return Ok(RunStatus {
+                name: self.name.clone(),
+                number_of_generations: config.number_of_generations,
+                progress: None,
+            });
+        }
+        let current_state = CurrentState::load_state(&state_path)?;
+        let number_of_generations = current_state
+            .config
+            .as_ref()
+            .map_or(config.number_of_generations, |config| {
+                config.number_of_generations
+            });
+        Ok(RunStatus {
+            name: self.name.clone(),
+            number_of_generations,
+            progress: Some(RunProgress {
+                generation: current_state.current_generation,
+                combinations_left: current_state.combinations_remaining(),
+                polynomials_found: current_state.interesting_polynomials.len(),
+                seed: current_state.seed,
+            }),
+        })
+    }
+}
+
+// Held while a process works on a run, removes the lock file when dropped.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Can not remove the lock {}: {}", self.path.display(), error);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn process_is_running(id: u32) -> bool {
+    // Signal 0 only checks that the process exists. EPERM means it exists but belongs to
+    // another user.
+    match libc::pid_t::try_from(id) {
+        Ok(pid) if pid > 0 => {
+            let result = unsafe { libc::kill(pid, 0) };
+            result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+        }
+        _ => false,
+    }
+}
+
+// Without a way to check, a lock is only stale once its file is deleted by hand.
+#[cfg(not(unix))]
+fn process_is_running(_id: u32) -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
+pub struct RunStatus {
+    pub name: String,
+    pub number_of_generations: usize,
+    // None until the first checkpoint is saved.
+    pub progress: Option<RunProgress>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunProgress {
+    // The generation being worked on, equal to the number of generations once the run is done.
+    pub generation: usize,
+    // Combinations of the current generation still to minimize.
+    pub combinations_left: usize,
+    pub polynomials_found: usize,
+    pub seed: u64,
+}
+
+impl RunStatus {
+    pub fn finished(&self) -> bool {
+        self.progress
+            .as_ref()
+            .is_some_and(|progress| progress.generation >= self.number_of_generations)
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.progress {
+            None => write!(f, "{}: not started", self.name),
+            Some(progress) if self.finished() => write!(
+                f,
+                "{}: finished {} generations, {} polynomials found",
+                self.name, self.number_of_generations, progress.polynomials_found
+            ),
+            Some(progress) => write!(
+                f,
+                "{}: generation {} of {}, {} combinations left, {} polynomials found",
+                self.name,
+                progress.generation + 1,
+                self.number_of_generations,
+                progress.combinations_left,
+                progress.polynomials_found
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "matrix_polynomial_analysis-runs-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn config() -> Config {
+        toml::from_str("matrix_size = 2\nmatrices_to_fuzz = 10\nnumber_of_generations = 3").unwrap()
+    }
+
+    #[test]
+    fn rejects_bad_names() {
+        let runs = Path::new("runs");
+        assert!(RunDirectory::new(runs, "first-run_1.0").is_ok());
+        for name in ["", ".", "..", "a/b", "a b"] {
+            assert!(
+                matches!(RunDirectory::new(runs, name), Err(Error::InvalidConfig(_))),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn creates_lists_and_reports_runs() {
+        let runs = runs_directory("create");
+        assert!(RunDirectory::list(&runs).unwrap().is_empty());
+        assert!(matches!(
+            RunDirectory::open(&runs, "first"),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let config = config();
+        for name in ["second", "first"] {
+            let run = RunDirectory::new(&runs, name).unwrap();
+            drop(run.create(&config).unwrap());
+            assert!(run.state_directory().is_dir());
+            assert!(run.output_directory().is_dir());
+        }
+        // Directories without a config are not runs.
+        fs::create_dir_all(runs.join("stray")).unwrap();
+        let names: Vec<String> = RunDirectory::list(&runs)
+            .unwrap()
+            .iter()
+            .map(|run| run.name().to_string())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+
+        let run = RunDirectory::open(&runs, "first").unwrap();
+        let status = run.status().unwrap();
+        assert_eq!(status.number_of_generations, 3);
+        assert!(status.progress.is_none());
+        assert!(!status.finished());
+        assert_eq!(status.to_string(), "first: not started");
+
+        let mut state = CurrentState::new(3, 1, 7);
+        state.set_state_path(&run.state_path());
+        state.save_state().unwrap();
+        let status = run.status().unwrap();
+        let progress = status.progress.as_ref().unwrap();
+        assert_eq!(progress.generation, 1);
+        assert_eq!(progress.combinations_left, state.combinations_remaining());
+        assert_eq!(progress.seed, 7);
+        assert!(!status.finished());
+
+        // A started run is resumed, not created again.
+        assert!(matches!(run.create(&config), Err(Error::InvalidConfig(_))));
+
+        let mut state = CurrentState::new(3, 3, 7);
+        state.set_state_path(&run.state_path());
+        state.save_state().unwrap();
+        assert!(run.status().unwrap().finished());
+        fs::remove_dir_all(&runs).unwrap();
+    }
+
+    #[test]
+    fn one_process_works_on_a_run() {
+        let runs = runs_directory("lock");
+        let run = RunDirectory::new(&runs, "locked").unwrap();
+        let lock = run.create(&config()).unwrap();
+        assert_eq!(
+            fs::read_to_string(run.lock_path()).unwrap(),
+            std::process::id().to_string()
+        );
+        let run = RunDirectory::open(&runs, "locked").unwrap();
+        assert!(matches!(run.lock(), Err(Error::InvalidConfig(_))));
+        drop(lock);
+        assert!(!run.lock_path().exists());
+        drop(run.lock().unwrap());
+
+        // No process has this id, pids stay below 2^22 on Linux.
+        if cfg!(unix) {
+            fs::write(run.lock_path(), i32::MAX.to_string()).unwrap();
+            let lock = run.lock().unwrap();
+            assert_eq!(
+                fs::read_to_string(run.lock_path()).unwrap(),
+                std::process::id().to_string()
+            );
+            drop(lock);
+        }
+        fs::remove_dir_all(&runs).unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+// Ctrl-C and SIGTERM only ask the run to stop. The mutate loop stops dispatching combinations,
+// minimizations that have not started are skipped, and the run saves a checkpoint and its partial
+// results before exiting. A second signal exits immediately.
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Whether a signal asked the run to stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+// Asks the run to stop, like a signal does.
+pub fn request() {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn handle_signal(signal: libc::c_int) {
+    // Only async signal safe calls here: the flag, and restoring the default action so the next
+    // signal terminates the process.
+    request();
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+// Installs the handler for SIGINT and SIGTERM. Without it a signal ends the run where it is, which
+// loses at most the work since the last checkpoint.
+#[cfg(unix)]
+pub fn install() {
+    let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            warn!(
+                "Can not handle signal {}: {}",
+                signal,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
@@ -0,0 +1,47 @@
+use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
+
+// Numeric tolerances used when deciding nonnegativity and minimizing coefficients. Loaded from the
+// `[tolerance]` section of the startup file, any missing value takes its default.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tolerance {
+    // Entries of p(A) down to -absolute count as nonnegative.
+    pub absolute: f64,
+    // Extra allowance as a fraction of the largest entry of p(A) in absolute value.
+    pub relative: f64,
+    // Coefficients within this distance of zero are treated as zero.
+    pub zero_coefficient: f64,
+    // A minimized polynomial is only kept when some coefficient is below this.
+    pub interesting_threshold: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            absolute: 0.0,
+            relative: 0.0,
+            zero_coefficient: 0.00001,
+            interesting_threshold: -0.1,
+        }
+    }
+}
+
+impl Tolerance {
+    // How far below zero an entry of the matrix may be while still counting as nonnegative.
+    pub fn allowance(&self, matrix: &DMatrix<f64>) -> f64 {
+        if self.relative == 0.0 {
+            return self.absolute;
+        }
+        self.absolute + self.relative * matrix.amax()
+    }
+
+    pub fn is_matrix_nonnegative(&self, matrix: &DMatrix<f64>) -> bool {
+        let allowance = self.allowance(matrix);
+        !matrix.iter().any(|value| value < &-allowance)
+    }
+
+    pub fn is_zero(&self, value: f64) -> bool {
+        value.abs() < self.zero_coefficient
+    }
+}
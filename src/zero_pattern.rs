@@ -0,0 +1,186 @@
+use crate::matrix_family;
+use itertools::Itertools;
+use nalgebra::DMatrix;
+use rand::{Rng, RngCore};
+use std::collections::HashSet;
+
+// Largest matrix size whose zero patterns are enumerated exhaustively. There are 2^(n^2) patterns
+// before removing isomorphic copies, so larger sizes fall back to random patterns.
+pub const MAX_ENUMERATED_SIZE: usize = 4;
+
+// Number of random patterns used above `MAX_ENUMERATED_SIZE`.
+pub const SAMPLED_PATTERNS: usize = 4096;
+
+// Largest matrix size with a zero pattern, one bit per entry of a `u64`.
+pub const MAX_PATTERN_SIZE: usize = 8;
+
+// The positions of the nonzero entries of a nonnegative matrix, i.e. the adjacency matrix of its
+// directed graph with loops. Bit `row * size + column` is set when that entry is nonzero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZeroPattern {
+    size: usize,
+    mask: u64,
+}
+
+impl ZeroPattern {
+    pub fn new(size: usize, mask: u64) -> Self {
+        assert!(
+            size <= MAX_PATTERN_SIZE,
+            "zero patterns support matrices up to 8x8"
+        );
+        ZeroPattern { size, mask }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_edge(&self, row: usize, column: usize) -> bool {
+        self.mask & (1 << (row * self.size + column)) != 0
+    }
+
+    pub fn edges(&self) -> usize {
+        self.mask.count_ones() as usize
+    }
+
+    // Relabels the vertices so vertex i becomes permutation[i]. Since
+    // p(P A P^T) = P p(A) P^T, relabeled patterns give the same verdict.
+    pub fn permute(&self, permutation: &[usize]) -> Self {
+        let mut mask = 0;
+        for row in 0..self.size {
+            for column in 0..self.size {
+                if self.is_edge(row, column) {
+                    mask |= 1 << (permutation[row] * self.size + permutation[column]);
+                }
+            }
+        }
+        ZeroPattern::new(self.size, mask)
+    }
+
+    // The smallest mask among all relabelings, shared by every pattern in the isomorphism class.
+    pub fn canonical(&self) -> Self {
+        (0..self.size)
+            .permutations(self.size)
+            .map(|permutation| self.permute(&permutation))
+            .min_by_key(|pattern| pattern.mask)
+            .unwrap_or(*self)
+    }
+
+    // A nonnegative matrix with this pattern and random positive weights on the edges.
+    pub fn weighted_matrix(&self, rng: &mut dyn RngCore) -> DMatrix<f64> {
+        let distribution = matrix_family::entry_distribution();
+        DMatrix::<f64>::from_fn(self.size, self.size, |row, column| {
+            if self.is_edge(row, column) {
+                rng.sample(distribution)
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+// One pattern from every isomorphism class of digraphs with loops on `size` vertices.
+pub fn nonisomorphic_zero_patterns(size: usize) -> Vec<ZeroPattern> {
+    let mut seen = HashSet::new();
+    let mut patterns = Vec::new();
+    for mask in 0..(1u64 << (size * size)) {
+        let canonical = ZeroPattern::new(size, mask).canonical();
+        if seen.insert(canonical) {
+            patterns.push(canonical);
+        }
+    }
+    patterns
+}
+
+pub fn random_zero_patterns(
+    size: usize,
+    number_of_patterns: usize,
+    rng: &mut dyn RngCore,
+) -> Vec<ZeroPattern> {
+    let entries = size * size;
+    (0..number_of_patterns)
+        .map(|_| {
+            let mask = rng.gen::<u64>();
+            let mask = if entries == 64 {
+                mask
+            } else {
+                mask & ((1 << entries) - 1)
+            };
+            ZeroPattern::new(size, mask)
+        })
+        .collect()
+}
+
+// Every isomorphism class for small matrices, otherwise a random selection of patterns.
+pub fn zero_patterns(size: usize, rng: &mut dyn RngCore) -> Vec<ZeroPattern> {
+    if size <= MAX_ENUMERATED_SIZE {
+        nonisomorphic_zero_patterns(size)
+    } else {
+        random_zero_patterns(size, SAMPLED_PATTERNS, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn counts_digraphs_with_loops() {
+        // OEIS A000595, digraphs with loops on n unlabeled vertices.
+        assert_eq!(nonisomorphic_zero_patterns(1).len(), 2);
+        assert_eq!(nonisomorphic_zero_patterns(2).len(), 10);
+        assert_eq!(nonisomorphic_zero_patterns(3).len(), 104);
+    }
+
+    #[test]
+    fn classes_are_canonical_and_distinct() {
+        let patterns = nonisomorphic_zero_patterns(3);
+        for pattern in &patterns {
+            assert_eq!(pattern.canonical(), *pattern);
+        }
+        let distinct: HashSet<ZeroPattern> = patterns.iter().copied().collect();
+        assert_eq!(distinct.len(), patterns.len());
+    }
+
+    #[test]
+    fn relabeling_keeps_the_class() {
+        // The path 0 -> 1 -> 2 with a loop at 0, relabeled by a rotation.
+        let pattern = ZeroPattern::new(3, 0b000_100_011);
+        let rotated = pattern.permute(&[1, 2, 0]);
+        assert_ne!(rotated, pattern);
+        assert_eq!(rotated.edges(), pattern.edges());
+        assert_eq!(rotated.canonical(), pattern.canonical());
+    }
+
+    #[test]
+    fn weighted_matrices_match_their_pattern() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for size in 1..=4 {
+            for pattern in zero_patterns(size, &mut rng) {
+                let matrix = pattern.weighted_matrix(&mut rng);
+                assert_eq!(matrix.shape(), (size, size));
+                for row in 0..size {
+                    for column in 0..size {
+                        if pattern.is_edge(row, column) {
+                            assert!(matrix[(row, column)] > 0.0);
+                        } else {
+                            assert_eq!(matrix[(row, column)], 0.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn large_sizes_sample_random_patterns() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let patterns = zero_patterns(MAX_PATTERN_SIZE, &mut rng);
+        assert_eq!(patterns.len(), SAMPLED_PATTERNS);
+        assert!(patterns
+            .iter()
+            .all(|pattern| pattern.get_size() == MAX_PATTERN_SIZE));
+    }
+}
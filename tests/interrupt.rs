@@ -0,0 +1,70 @@
+// A run asked to stop before it minimizes anything saves its state with every combination still
+// to do, and its results are marked as interrupted. This sets the process wide shutdown flag, so it
+// has a test binary of its own.
+use matrix_polynomial_analysis::config::Config;
+use matrix_polynomial_analysis::current_state::CurrentState;
+use matrix_polynomial_analysis::output::{Output, RunMetadata};
+use matrix_polynomial_analysis::polynomial::Polynomial;
+use matrix_polynomial_analysis::*;
+use std::fs;
+use std::time::SystemTime;
+
+fn config() -> Config {
+    toml::from_str(
+        r#"
+        matrix_size = 2
+        matrices_to_fuzz = 200
+        mutated_polynomials_to_evaluate = 4
+        number_of_generations = 2
+        workers = 2
+
+        [progress]
+        display = false
+        "#,
+    )
+    .expect("the test config is valid")
+}
+
+#[test]
+fn stops_before_the_first_combination() {
+    let directory = std::env::temp_dir().join(format!(
+        "matrix_polynomial_analysis-interrupt-{}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let state_path = directory.join("state.json");
+    let config = config();
+    let polynomial = Polynomial::from_element(5, 2, 1.0);
+
+    shutdown::request();
+    let current_state =
+        mutate_polynomial_from_beginning(polynomial.clone(), &config, 42, &state_path).unwrap();
+    let every_combination = CurrentState::new(polynomial.len(), 0, 42);
+    assert_eq!(current_state.current_generation, 0);
+    assert_eq!(
+        current_state.combinations_left,
+        every_combination.combinations_left
+    );
+    assert!(current_state.interesting_polynomials.is_empty());
+
+    let saved = CurrentState::load_state(&state_path).unwrap();
+    assert_eq!(saved.current_generation, 0);
+    assert_eq!(saved.combinations_left, every_combination.combinations_left);
+    assert_eq!(
+        saved.starting_mutated_polynomials.len(),
+        current_state.starting_mutated_polynomials.len()
+    );
+
+    let run = RunMetadata {
+        matrix_size: 2,
+        polynomial_length: polynomial.len(),
+        mutated_polynomials_to_evaluate: config.mutated_polynomials_to_evaluate,
+        matrices_to_fuzz: config.matrices_to_fuzz,
+        imported_from: None,
+    };
+    let output = Output::from_state(current_state, config, run, SystemTime::now());
+    assert!(output.interrupted);
+    assert_eq!(output.seed, Some(42));
+    fs::remove_dir_all(&directory).unwrap();
+}
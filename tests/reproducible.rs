@@ -0,0 +1,145 @@
+// Runs with the same seed find the same polynomials, and a run resumed from a checkpoint saved part
+// way through finishes exactly like a run that was never stopped.
+use matrix_polynomial_analysis::config::Config;
+use matrix_polynomial_analysis::current_state::CurrentState;
+use matrix_polynomial_analysis::polynomial::Polynomial;
+use matrix_polynomial_analysis::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SEED: u64 = 42;
+
+fn config() -> Config {
+    toml::from_str(
+        r#"
+        matrix_size = 2
+        matrices_to_fuzz = 2000
+        mutated_polynomials_to_evaluate = 4
+        number_of_generations = 2
+        workers = 2
+        matrix_families = ["circulant", "upper_triangular"]
+
+        [checkpoint]
+        interval_seconds = 0.0
+        keep = 1000
+
+        [progress]
+        display = false
+        "#,
+    )
+    .expect("the test config is valid")
+}
+
+fn starting_polynomial() -> Polynomial {
+    Polynomial::from_element(5, 2, 1.0)
+}
+
+// An empty directory for the files of one test.
+fn test_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "matrix_polynomial_analysis-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn run(directory: &Path, seed: u64) -> CurrentState {
+    fs::create_dir_all(directory).unwrap();
+    mutate_polynomial_from_beginning(
+        starting_polynomial(),
+        &config(),
+        seed,
+        &directory.join("state.json"),
+    )
+    .unwrap()
+}
+
+fn coefficients(current_state: &CurrentState) -> Vec<Vec<f64>> {
+    current_state
+        .interesting_polynomials
+        .iter()
+        .map(|polynomial| polynomial.coefficients().to_vec())
+        .collect()
+}
+
+#[test]
+fn same_seed_mutates_the_same_polynomials() {
+    let polynomial = starting_polynomial();
+    let mutate = |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        initialize_current_state(&polynomial, 20, seed, &mut rng).starting_mutated_polynomials
+    };
+    let coefficients = |polynomials: Vec<Polynomial>| -> Vec<Vec<f64>> {
+        polynomials
+            .iter()
+            .map(|polynomial| polynomial.coefficients().to_vec())
+            .collect()
+    };
+    assert_eq!(coefficients(mutate(SEED)), coefficients(mutate(SEED)));
+    assert_ne!(coefficients(mutate(SEED)), coefficients(mutate(SEED + 1)));
+}
+
+#[test]
+fn same_seed_finds_the_same_polynomials() {
+    let directory = test_directory("same-seed");
+    let first = run(&directory.join("first"), SEED);
+    let second = run(&directory.join("second"), SEED);
+    assert_eq!(first.current_generation, 2);
+    assert!(!first.interesting_polynomials.is_empty());
+    assert_eq!(coefficients(&first), coefficients(&second));
+    assert_eq!(first.provenance, second.provenance);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn resumed_run_matches_a_straight_run() {
+    let directory = test_directory("resume");
+    let straight_directory = directory.join("straight");
+    let straight = run(&straight_directory, SEED);
+    assert!(!straight.interesting_polynomials.is_empty());
+
+    // Every combination was checkpointed, `state.json.<n>` is the state n saves before the end.
+    // Take one from the middle of the first generation, as a crash there would have left it.
+    let state_path = straight_directory.join("state.json");
+    let total = straight.combinations_total();
+    let checkpoint = (1..)
+        .map(|i| {
+            let mut name = state_path.clone().into_os_string();
+            name.push(format!(".{}", i));
+            PathBuf::from(name)
+        })
+        .take_while(|path| path.exists())
+        .find(|path| {
+            let state = CurrentState::load_state(path).unwrap();
+            state.current_generation == 0 && state.combinations_remaining() == total / 2
+        })
+        .expect("a checkpoint from the middle of the first generation");
+
+    let resumed_directory = directory.join("resumed");
+    fs::create_dir_all(&resumed_directory).unwrap();
+    let resumed_state_path = resumed_directory.join("state.json");
+    fs::copy(&checkpoint, &resumed_state_path).unwrap();
+    let interrupted = CurrentState::load_state(&resumed_state_path).unwrap();
+    let config = interrupted
+        .config
+        .clone()
+        .expect("the state records the config");
+    let verifier_config = config.verifier_config(interrupted.seed).unwrap();
+    let resumed = mutate_polynomial(
+        interrupted,
+        &verifier_config,
+        config.number_of_generations,
+        &config.progress,
+    )
+    .unwrap();
+
+    assert_eq!(resumed.current_generation, straight.current_generation);
+    assert_eq!(coefficients(&resumed), coefficients(&straight));
+    assert_eq!(resumed.provenance, straight.provenance);
+    fs::remove_dir_all(&directory).unwrap();
+}